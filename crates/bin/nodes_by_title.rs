@@ -16,7 +16,7 @@ fn main() {
     let node = Node::by_title(node_title, &mut conn).expect("internal error when search a node");
 
     let childs = node
-        .refers_to(&mut conn)
+        .refers_to(&conn)
         .expect("couldn't find ndoes which refers to a given node");
 
     println!("> Links inside this node");
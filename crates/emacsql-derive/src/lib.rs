@@ -0,0 +1,145 @@
+//! `#[derive(FromRow)]` for the `emacsql` crate.
+//!
+//! Every field of a struct is read with `emacsql::Row::get` from the column
+//! with the same name.  Fields support the following attributes:
+//!
+//! - `#[emacsql(rename = "file")]` reads a field from another column;
+//! - `#[emacsql(default)]` uses `Default::default()` when a query doesn't
+//!   select the column, `Option` fields behave like that without the attribute;
+//! - `#[emacsql(skip)]` never reads a field and always uses
+//!   `Default::default()`.
+//!
+//! Fields of tuple structs are read by the index of a column.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Field, Fields, LitStr, Type};
+
+#[proc_macro_derive(FromRow, attributes(emacsql))]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "FromRow can be derived only for structs",
+            ))
+        }
+    };
+
+    let body = match fields {
+        Fields::Named(fields) => {
+            let inits = fields
+                .named
+                .iter()
+                .map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    let column = ident.to_string();
+                    let value = field_value(field, quote!(#column))?;
+                    Ok(quote!(#ident: #value))
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(Self { #(#inits),* })
+        }
+        Fields::Unnamed(fields) => {
+            let values = fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(i, field)| field_value(field, quote!(#i)))
+                .collect::<syn::Result<Vec<_>>>()?;
+            quote!(Self(#(#values),*))
+        }
+        Fields::Unit => quote!(Self),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::emacsql::FromRow for #name #ty_generics #where_clause {
+            fn try_from_row(row: &::emacsql::Row) -> ::emacsql::Result<Self> {
+                ::core::result::Result::Ok(#body)
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<LitStr>,
+    default: bool,
+    skip: bool,
+}
+
+impl FieldAttrs {
+    fn parse(field: &Field) -> syn::Result<Self> {
+        let mut attrs = FieldAttrs::default();
+        for attr in field.attrs.iter().filter(|a| a.path().is_ident("emacsql")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("rename") {
+                    attrs.rename = Some(meta.value()?.parse()?);
+                } else if meta.path.is_ident("default") {
+                    attrs.default = true;
+                } else if meta.path.is_ident("skip") {
+                    attrs.skip = true;
+                } else {
+                    return Err(meta.error("unknown emacsql attribute"));
+                }
+                Ok(())
+            })?;
+        }
+        Ok(attrs)
+    }
+}
+
+/// Return the expression which reads a field from `row`, `column` is the
+/// default name or index of its column.
+fn field_value(field: &Field, column: TokenStream2) -> syn::Result<TokenStream2> {
+    let attrs = FieldAttrs::parse(field)?;
+    if attrs.skip {
+        return Ok(quote!(::core::default::Default::default()));
+    }
+
+    let column = match attrs.rename {
+        Some(name) if field.ident.is_none() => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "fields of tuple structs are read by index and can't be renamed",
+            ))
+        }
+        Some(name) => quote!(#name),
+        None => column,
+    };
+
+    if field.ident.is_some() && (attrs.default || is_option(&field.ty)) {
+        Ok(quote! {
+            if row.has_column(#column) {
+                row.get(#column)?
+            } else {
+                ::core::default::Default::default()
+            }
+        })
+    } else {
+        Ok(quote!(row.get(#column)?))
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(ty) => ty
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
@@ -0,0 +1,609 @@
+//! Compile EmacSQL s-expression queries into SQL.
+//!
+//! EmacSQL statements are vectors of keywords and their arguments:
+//! `[:select [id title] :from nodes :where (= id $s1)]` is compiled into
+//! `SELECT id, title FROM nodes WHERE id = ?;`.  A keyword becomes an upper
+//! case SQL keyword (`:order-by` is `ORDER BY`), symbols are identifiers
+//! (`nodes:node-id` is `nodes.node_id`) and lists are expressions.
+//!
+//! Parameters refer to the arguments of [`compile`]:
+//!
+//! - `$s1` is a scalar, it's bound to the statement like EmacSQL stores it;
+//! - `$v1` is a vector of scalars, like `(?, ?, ?)`, or a list of such
+//!   vectors, like `(?, ?), (?, ?)` for rows of `:values`;
+//! - `$i1` is an identifier, a symbol or a string;
+//! - `$r1` is a raw SQL string.
+
+use std::fmt::{self, Display};
+
+use rusqlite::Connection;
+
+use crate::lisp;
+use crate::params::ToEmacsql;
+use crate::query::QueryTyped;
+use crate::row::FromRow;
+use crate::value::Value;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Read(lisp::Error),
+    /// a statement isn't a vector which starts with a keyword
+    NotAStatement(lisp::Value),
+    InvalidExpr(lisp::Value),
+    /// a parameter like `$s3` refers to an argument which isn't passed
+    MissingArg(usize),
+    /// an argument has the wrong type for its parameter, like a number for `$i1`
+    InvalidArg(usize),
+    UnknownParam(String),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "can't read a statement: {err}"),
+            Self::NotAStatement(val) => {
+                write!(f, "a statement must be a vector of keywords, got {val}")
+            }
+            Self::InvalidExpr(val) => write!(f, "invalid expression {val}"),
+            Self::MissingArg(n) => write!(f, "argument {n} is missing"),
+            Self::InvalidArg(n) => write!(f, "argument {n} has a wrong type"),
+            Self::UnknownParam(name) => write!(f, "unknown parameter {name}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<lisp::Error> for Error {
+    fn from(err: lisp::Error) -> Self {
+        Self::Read(err)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// A compiled statement and the values of its `?` placeholders.
+#[derive(Debug, PartialEq)]
+pub struct Query {
+    pub sql: String,
+    pub params: Vec<Value>,
+}
+
+impl Query {
+    /// Run the query and convert every row with [`FromRow`], columns are
+    /// decoded according to their declared types.
+    pub fn query_as<T: FromRow>(&self, conn: &Connection) -> crate::Result<Vec<T>> {
+        conn.query_typed(&self.sql, rusqlite::params_from_iter(&self.params))
+    }
+
+    /// Run the statement, return the number of changed rows.
+    pub fn execute(&self, conn: &Connection) -> crate::Result<usize> {
+        conn.execute(&self.sql, rusqlite::params_from_iter(&self.params))
+    }
+}
+
+/// Compile a statement like `[:select * :from nodes]` with the arguments of
+/// its parameters.
+pub fn compile(statement: &lisp::Value, args: &[lisp::Value]) -> Result<Query> {
+    let mut compiler = Compiler {
+        args,
+        params: Vec::new(),
+    };
+    let sql = match statement {
+        lisp::Value::Vector(items) if starts_with_keyword(items) => compiler.statement(items)?,
+        _ => return Err(Error::NotAStatement(statement.clone())),
+    };
+    Ok(Query {
+        sql: sql + ";",
+        params: compiler.params,
+    })
+}
+
+/// Compile a single expression without parameters, like `(> level 0)`.
+pub(crate) fn compile_expr(expr: &lisp::Value) -> Result<String> {
+    let mut compiler = Compiler {
+        args: &[],
+        params: Vec::new(),
+    };
+    compiler.expr(expr, false)
+}
+
+/// Read a statement from its source and compile it, see [`compile`].
+pub fn compile_str(src: &str, args: &[lisp::Value]) -> Result<Query> {
+    compile(&src.parse()?, args)
+}
+
+// binary operators which are written between their operands
+macro_rules! infix_ops {
+    () => {
+        "=" | "!="
+            | "<>"
+            | "<"
+            | ">"
+            | "<="
+            | ">="
+            | "like"
+            | "glob"
+            | "regexp"
+            | "match"
+            | "is"
+            | "is-not"
+            | "in"
+            | "not-in"
+            | "/"
+            | "%"
+            | "&"
+            | "|"
+            | "<<"
+            | ">>"
+    };
+}
+
+struct Compiler<'a> {
+    args: &'a [lisp::Value],
+    params: Vec<Value>,
+}
+
+impl Compiler<'_> {
+    fn statement(&mut self, items: &[lisp::Value]) -> Result<String> {
+        let mut parts = Vec::new();
+        let mut keyword = String::new();
+        // the number of arguments after the last keyword
+        let mut n = 0;
+        for item in items {
+            if let Some(name) = keyword_name(item) {
+                keyword = name.replace('-', " ").to_uppercase();
+                parts.push(keyword.clone());
+                n = 0;
+                continue;
+            }
+            parts.push(self.clause_arg(item, &keyword, n)?);
+            n += 1;
+        }
+        Ok(parts.join(" "))
+    }
+
+    /// Compile the `n`th argument of a keyword.
+    ///
+    /// A vector is a list of columns or expressions, it's wrapped into
+    /// parentheses after `VALUES` or another argument, like the columns in
+    /// `:insert :into nodes [id title]`.
+    fn clause_arg(&mut self, item: &lisp::Value, keyword: &str, n: usize) -> Result<String> {
+        match item {
+            lisp::Value::Vector(items) if !starts_with_keyword(items) => {
+                if keyword == "VALUES" || n > 0 {
+                    self.expr(item, true)
+                } else {
+                    self.comma_separated(items)
+                }
+            }
+            lisp::Value::List(rows)
+                if keyword == "VALUES"
+                    && rows.iter().all(|r| matches!(r, lisp::Value::Vector(_))) =>
+            {
+                let rows = rows
+                    .iter()
+                    .map(|row| self.expr(row, true))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(rows.join(", "))
+            }
+            _ => self.expr(item, false),
+        }
+    }
+
+    fn comma_separated(&mut self, items: &[lisp::Value]) -> Result<String> {
+        let items = items
+            .iter()
+            .map(|item| self.expr(item, false))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(items.join(", "))
+    }
+
+    /// Compile an expression, `nested` expressions with operators are wrapped
+    /// into parentheses.
+    fn expr(&mut self, val: &lisp::Value, nested: bool) -> Result<String> {
+        match val {
+            lisp::Value::Integer(_) | lisp::Value::Real(_) => Ok(val.to_lisp_string()),
+            lisp::Value::Nil => Ok("NULL".to_string()),
+            lisp::Value::String(_) | lisp::Value::Propertized(..) => Ok(scalar(val)),
+            lisp::Value::Symbol(name) if name.starts_with('$') => self.param(name),
+            lisp::Value::Symbol(name) if name.starts_with(':') => Ok(scalar(val)),
+            lisp::Value::Symbol(name) => Ok(identifier(name)),
+            lisp::Value::Vector(items) if starts_with_keyword(items) => {
+                Ok(format!("({})", self.statement(items)?))
+            }
+            lisp::Value::Vector(items) => Ok(format!("({})", self.comma_separated(items)?)),
+            lisp::Value::List(items) => match items.as_slice() {
+                [lisp::Value::Symbol(quote), quoted] if quote == "quote" => Ok(scalar(quoted)),
+                [lisp::Value::Symbol(op), args @ ..] => {
+                    let sql = self.operator(op, args, val)?;
+                    if nested && !is_call(op) {
+                        Ok(format!("({sql})"))
+                    } else {
+                        Ok(sql)
+                    }
+                }
+                _ => Err(Error::InvalidExpr(val.clone())),
+            },
+            _ => Err(Error::InvalidExpr(val.clone())),
+        }
+    }
+
+    /// Compile the expression `(op args...)`, `val` is the whole expression.
+    fn operator(&mut self, op: &str, args: &[lisp::Value], val: &lisp::Value) -> Result<String> {
+        let invalid = || Error::InvalidExpr(val.clone());
+        // `is-not` is `IS NOT`, but `-` is subtraction
+        let sql_op = match op {
+            "-" => op.to_string(),
+            _ => op.replace('-', " ").to_uppercase(),
+        };
+        match (op, args) {
+            ("-", [arg]) => Ok(format!("-{}", self.expr(arg, true)?)),
+            ("and" | "or" | "+" | "-" | "*" | "||", [_, ..]) | (infix_ops!(), [_, _]) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.expr(arg, true))
+                    .collect::<Result<Vec<_>>>()?;
+                Ok(args.join(&format!(" {sql_op} ")))
+            }
+            ("not" | "distinct" | "exists" | "not-exists", [arg]) => {
+                Ok(format!("{sql_op} {}", self.expr(arg, true)?))
+            }
+            ("asc" | "desc" | "isnull" | "notnull", [arg]) => {
+                Ok(format!("{} {sql_op}", self.expr(arg, true)?))
+            }
+            ("as", [expr, alias]) => Ok(format!(
+                "{} AS {}",
+                self.expr(expr, true)?,
+                self.expr(alias, true)?
+            )),
+            ("between" | "not-between", [expr, low, high]) => Ok(format!(
+                "{} {sql_op} {} AND {}",
+                self.expr(expr, true)?,
+                self.expr(low, true)?,
+                self.expr(high, true)?
+            )),
+            ("funcall", [lisp::Value::Symbol(name), args @ ..]) => self.call(name, args),
+            (infix_ops!() | "and" | "or" | "-" | "not" | "as" | "funcall", _) => Err(invalid()),
+            (name, args) => self.call(name, args),
+        }
+    }
+
+    fn call(&mut self, name: &str, args: &[lisp::Value]) -> Result<String> {
+        Ok(format!(
+            "{}({})",
+            identifier(name),
+            self.comma_separated(args)?
+        ))
+    }
+
+    fn param(&mut self, name: &str) -> Result<String> {
+        let unknown = || Error::UnknownParam(name.to_string());
+        let mut chars = name[1..].chars();
+        let kind = chars.next().ok_or_else(unknown)?;
+        let n: usize = chars.as_str().parse().map_err(|_| unknown())?;
+        if !matches!(kind, 's' | 'v' | 'i' | 'r') {
+            return Err(unknown());
+        }
+        let arg = n
+            .checked_sub(1)
+            .and_then(|i| self.args.get(i))
+            .ok_or(Error::MissingArg(n))?;
+        match (kind, arg) {
+            ('s', arg) => {
+                self.params.push(arg.to_emacsql());
+                Ok("?".to_string())
+            }
+            // a list of rows, like `emacsql-escape-vector` reads it
+            ('v', lisp::Value::List(rows))
+                if rows.iter().all(|row| matches!(row, lisp::Value::Vector(_))) =>
+            {
+                let groups: Vec<_> = rows.iter().map(|row| self.param_group(row)).collect();
+                Ok(groups.join(", "))
+            }
+            // a list of scalars is one row too, since rust slices are lists
+            ('v', items @ (lisp::Value::List(_) | lisp::Value::Vector(_))) => {
+                Ok(self.param_group(items))
+            }
+            ('v', lisp::Value::Nil) => Ok("()".to_string()),
+            ('i', lisp::Value::Symbol(s) | lisp::Value::String(s)) => Ok(identifier(s)),
+            ('r', lisp::Value::String(s)) => Ok(s.clone()),
+            _ => Err(Error::InvalidArg(n)),
+        }
+    }
+
+    /// Bind the items of a list or a vector, return their placeholders like
+    /// `(?, ?)`.
+    fn param_group(&mut self, items: &lisp::Value) -> String {
+        let start = self.params.len();
+        self.params.extend(items.iter().map(ToEmacsql::to_emacsql));
+        format!("({})", vec!["?"; self.params.len() - start].join(", "))
+    }
+}
+
+/// Return true, if `(op ...)` is compiled into a function call.
+fn is_call(op: &str) -> bool {
+    !matches!(
+        op,
+        infix_ops!()
+            | "and"
+            | "or"
+            | "+"
+            | "*"
+            | "||"
+            | "-"
+            | "not"
+            | "distinct"
+            | "exists"
+            | "not-exists"
+            | "asc"
+            | "desc"
+            | "isnull"
+            | "notnull"
+            | "as"
+            | "between"
+            | "not-between"
+    )
+}
+
+fn keyword_name(val: &lisp::Value) -> Option<&str> {
+    match val {
+        lisp::Value::Symbol(name) => name.strip_prefix(':').filter(|s| !s.is_empty()),
+        _ => None,
+    }
+}
+
+fn starts_with_keyword(items: &[lisp::Value]) -> bool {
+    items.first().and_then(keyword_name).is_some()
+}
+
+/// Return a value as a SQL string literal of its lisp representation, the
+/// same way EmacSQL stores it: `"id"` becomes `'"id"'`.
+fn scalar(val: &lisp::Value) -> String {
+    format!("'{}'", val.to_lisp_string().replace('\'', "''"))
+}
+
+// words which can't be identifiers without quotes
+const RESERVED: &[&str] = &[
+    "all",
+    "and",
+    "as",
+    "asc",
+    "between",
+    "by",
+    "case",
+    "create",
+    "default",
+    "delete",
+    "desc",
+    "distinct",
+    "drop",
+    "else",
+    "exists",
+    "from",
+    "group",
+    "having",
+    "in",
+    "index",
+    "insert",
+    "into",
+    "is",
+    "join",
+    "limit",
+    "not",
+    "null",
+    "offset",
+    "on",
+    "or",
+    "order",
+    "primary",
+    "references",
+    "select",
+    "set",
+    "table",
+    "then",
+    "union",
+    "unique",
+    "update",
+    "values",
+    "when",
+    "where",
+];
+
+/// Return a symbol as a SQL identifier: `-` is replaced with `_` and `:` with
+/// `.`, so `nodes:node-id` is `nodes.node_id`.
+pub(crate) fn identifier(name: &str) -> String {
+    let name = name.replace('-', "_").replace(':', ".");
+    let plain = name == "*"
+        || (!name.starts_with(|c: char| c.is_ascii_digit())
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+            && !RESERVED.contains(&name.to_lowercase().as_str()));
+    if plain {
+        name
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ToLisp;
+
+    fn sql(src: &str, args: &[lisp::Value]) -> String {
+        compile_str(src, args).unwrap().sql
+    }
+
+    #[test]
+    fn test_compile_select() {
+        let query = compile_str(
+            "[:select [id title] :from nodes :where (= id $s1)]",
+            &["x".to_lisp()],
+        )
+        .unwrap();
+        assert_eq!(query.sql, "SELECT id, title FROM nodes WHERE id = ?;");
+        assert_eq!(
+            query.params,
+            vec![Value::Lisp(lisp::Value::String("x".into()))]
+        );
+    }
+
+    #[test]
+    fn test_compile_expressions() {
+        assert_eq!(
+            sql("[:select (- a b) :from t]", &[]),
+            "SELECT a - b FROM t;"
+        );
+        assert_eq!(
+            sql("[:select [(- a) (- a b (* c 2))] :from t]", &[]),
+            "SELECT -a, a - b - (c * 2) FROM t;"
+        );
+        assert_eq!(
+            sql(
+                "[:select (funcall count *) :from nodes :where (and (= level 0) (like title \"%a%\"))]",
+                &[]
+            ),
+            "SELECT count(*) FROM nodes WHERE (level = 0) AND (title LIKE '\"%a%\"');"
+        );
+        assert_eq!(
+            sql(
+                "[:select :distinct [nodes:id (as tags:tag t)] :from nodes :left-join tags \
+                 :on (= nodes:id tags:node-id) :order-by [(desc nodes:title)] :limit 10]",
+                &[]
+            ),
+            "SELECT DISTINCT nodes.id, tags.tag AS t FROM nodes LEFT JOIN tags \
+             ON nodes.id = tags.node_id ORDER BY nodes.title DESC LIMIT 10;"
+        );
+        assert_eq!(
+            sql(
+                "[:select * :from links :where (not (in type ['id \"https\"]))]",
+                &[]
+            ),
+            "SELECT * FROM links WHERE NOT (type IN ('id', '\"https\"'));"
+        );
+        assert_eq!(
+            sql(
+                "[:select [id] :from nodes :where (in id [:select node-id :from tags])]",
+                &[]
+            ),
+            "SELECT id FROM nodes WHERE id IN (SELECT node_id FROM tags);"
+        );
+    }
+
+    #[test]
+    fn test_compile_insert() {
+        let query = compile_str(
+            "[:insert :into nodes [id level] :values ([\"a\" 1] [$s1 nil])]",
+            &["it's".to_lisp()],
+        )
+        .unwrap();
+        assert_eq!(
+            query.sql,
+            "INSERT INTO nodes (id, level) VALUES ('\"a\"', 1), (?, NULL);"
+        );
+        assert_eq!(query.params.len(), 1);
+    }
+
+    #[test]
+    fn test_compile_params() {
+        let query = compile_str(
+            "[:select * :from $i1 :where (in tag $v2) :limit $r3]",
+            &[
+                lisp::Value::Symbol("tags".into()),
+                vec!["a", "b"].to_lisp(),
+                "5".to_lisp(),
+            ],
+        )
+        .unwrap();
+        assert_eq!(query.sql, "SELECT * FROM tags WHERE tag IN (?, ?) LIMIT 5;");
+        assert_eq!(query.params.len(), 2);
+
+        let err = |src| compile_str(src, &[]).unwrap_err();
+        assert_eq!(err("[:select * :from $i1]"), Error::MissingArg(1));
+        assert_eq!(
+            err("[:select * :from $x1]"),
+            Error::UnknownParam("$x1".into())
+        );
+        assert!(matches!(err("(select *)"), Error::NotAStatement(_)));
+        assert!(matches!(err("[:select (= 1)]"), Error::InvalidExpr(_)));
+    }
+
+    #[test]
+    fn test_identifiers() {
+        assert_eq!(identifier("node-id"), "node_id");
+        assert_eq!(identifier("order"), "\"order\"");
+        assert_eq!(identifier("a b"), "\"a b\"");
+    }
+
+    #[test]
+    fn test_run_query() {
+        use crate::row::Row;
+
+        struct Node {
+            id: String,
+            title: String,
+        }
+
+        impl FromRow for Node {
+            fn try_from_row(row: &Row) -> crate::Result<Self> {
+                Ok(Self {
+                    id: row.get("id")?,
+                    title: row.get("title")?,
+                })
+            }
+        }
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE nodes (id, title)")
+            .unwrap();
+        compile_str(
+            "[:insert :into nodes :values [$s1 $s2]]",
+            &["1".to_lisp(), "Momentum".to_lisp()],
+        )
+        .unwrap()
+        .execute(&conn)
+        .unwrap();
+
+        let nodes: Vec<Node> = compile_str(
+            "[:select [id title] :from nodes :where (= id $s1)]",
+            &["1".to_lisp()],
+        )
+        .unwrap()
+        .query_as(&conn)
+        .unwrap();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!((&*nodes[0].id, &*nodes[0].title), ("1", "Momentum"));
+    }
+
+    #[test]
+    fn test_insert_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE tags (node_id, tag)")
+            .unwrap();
+        let rows: lisp::Value = r#"(["a" "b"] ["c" "d"])"#.parse().unwrap();
+        let query = compile_str("[:insert :into tags :values $v1]", &[rows]).unwrap();
+        assert_eq!(query.sql, "INSERT INTO tags VALUES (?, ?), (?, ?);");
+        query.execute(&conn).unwrap();
+
+        let one: lisp::Value = r#"["e" "f"]"#.parse().unwrap();
+        compile_str("[:insert :into tags :values $v1]", &[one])
+            .unwrap()
+            .execute(&conn)
+            .unwrap();
+
+        let tags: Vec<(String, String)> = compile_str(
+            "[:select [node-id tag] :from tags :order-by [node-id]]",
+            &[],
+        )
+        .unwrap()
+        .query_as(&conn)
+        .unwrap();
+        let expected = [("a", "b"), ("c", "d"), ("e", "f")];
+        assert_eq!(
+            tags,
+            expected.map(|(id, tag)| (id.to_string(), tag.to_string()))
+        );
+    }
+}
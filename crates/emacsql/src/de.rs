@@ -0,0 +1,614 @@
+//! Deserialize rust values from Emacs Lisp values with `serde`.
+//!
+//! Structs and maps are read from plists (`(:id "x" :level 1)`), alists
+//! (`(("ID" . "x"))`) and hash tables, sequences and tuples from lists,
+//! vectors and conses.  The leading `:` of a keyword is dropped when it's
+//! used as the name of a field or of an enum variant.
+
+use std::fmt::{self, Display};
+
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
+    VariantAccess, Visitor,
+};
+
+use crate::error::FromEmacsqlError;
+use crate::lisp::{self, into_pairs, is_alist, is_plist, Value};
+use crate::value::FromLisp;
+
+#[derive(Debug, PartialEq)]
+pub struct Error(String);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+
+impl From<lisp::Error> for Error {
+    fn from(err: lisp::Error) -> Self {
+        Error(err.to_string())
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Deserialize an instance of `T` from a lisp value.
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T> {
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Deserialize an instance of `T` from the text of a lisp value.
+pub fn from_str<T: DeserializeOwned>(s: &str) -> Result<T> {
+    from_value(lisp::parse_string(s)?)
+}
+
+/// A wrapper which decodes a column with `serde` instead of [`FromLisp`].
+///
+/// `row.get::<_, Serde<Properties>>("properties")?.0` reads the properties
+/// alist of an org-roam node into any `Deserialize` struct.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Serde<T>(pub T);
+
+impl<T: DeserializeOwned> FromLisp for Serde<T> {
+    fn from_lisp(sexp: Value) -> Result<Self, FromEmacsqlError> {
+        from_value(sexp)
+            .map(Serde)
+            .map_err(|err| FromEmacsqlError::Other(Box::new(err)))
+    }
+}
+
+pub struct Deserializer {
+    value: Value,
+}
+
+impl Deserializer {
+    pub fn new(value: Value) -> Self {
+        Deserializer { value }
+    }
+
+    fn invalid_type(&self, exp: &dyn de::Expected) -> Error {
+        de::Error::invalid_type(unexpected(&self.value), exp)
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Deserializer;
+
+    fn into_deserializer(self) -> Deserializer {
+        Deserializer::new(self)
+    }
+}
+
+fn unexpected(value: &Value) -> de::Unexpected<'_> {
+    use de::Unexpected;
+
+    match value {
+        Value::String(s) | Value::Propertized(s, _) => Unexpected::Str(s),
+        Value::Unibyte(bytes) => Unexpected::Bytes(bytes),
+        Value::Symbol(_) => Unexpected::Other("symbol"),
+        Value::Integer(n) => Unexpected::Signed(*n),
+        Value::BigInt(_) => Unexpected::Other("bignum"),
+        Value::Real(n) => Unexpected::Float(*n),
+        Value::Nil => Unexpected::Unit,
+        Value::List(_) => Unexpected::Other("list"),
+        Value::Vector(_) => Unexpected::Other("vector"),
+        Value::Cons(..) => Unexpected::Other("cons"),
+        Value::HashTable { .. } => Unexpected::Map,
+        Value::Record(_) => Unexpected::Other("record"),
+    }
+}
+
+/// Return the items of a sequence, the tail of an improper list is its last
+/// item.
+fn into_items(value: Value) -> Result<Vec<Value>, Value> {
+    match value {
+        Value::List(items) | Value::Vector(items) | Value::Record(items) => Ok(items),
+        Value::Nil => Ok(Vec::new()),
+        Value::Cons(car, cdr) => {
+            let mut items = vec![*car];
+            let mut tail = *cdr;
+            while let Value::Cons(car, cdr) = tail {
+                items.push(*car);
+                tail = *cdr;
+            }
+            items.push(tail);
+            Ok(items)
+        }
+        value => Err(value),
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::String(s) | Value::Propertized(s, _) => visitor.visit_string(s),
+            Value::Unibyte(bytes) => visitor.visit_byte_buf(bytes),
+            Value::Symbol(s) if s == "t" => visitor.visit_bool(true),
+            Value::Symbol(s) => visitor.visit_string(s),
+            Value::Integer(n) => visitor.visit_i64(n),
+            Value::BigInt(n) => match (n.parse(), n.parse()) {
+                (Ok(n), _) => visitor.visit_i128(n),
+                (_, Ok(n)) => visitor.visit_u128(n),
+                _ => visitor.visit_string(n),
+            },
+            Value::Real(n) => visitor.visit_f64(n),
+            Value::Nil => visitor.visit_unit(),
+            Value::HashTable { data, .. } => visit_map(data, visitor),
+            value => visit_seq(into_items(value).unwrap(), visitor),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Nil => visitor.visit_bool(false),
+            Value::Symbol(ref s) if s == "t" => visitor.visit_bool(true),
+            _ => Err(self.invalid_type(&"t or nil")),
+        }
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Integer(n) => match u32::try_from(n).ok().and_then(char::from_u32) {
+                Some(ch) => visitor.visit_char(ch),
+                None => Err(self.invalid_type(&"a character")),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::String(s) | Value::Propertized(s, _) | Value::Symbol(s) => {
+                visitor.visit_string(s)
+            }
+            Value::Unibyte(ref bytes) => match std::str::from_utf8(bytes) {
+                Ok(s) => visitor.visit_str(s),
+                Err(_) => Err(self.invalid_type(&"a UTF-8 string")),
+            },
+            _ => Err(self.invalid_type(&"a string")),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::String(s) | Value::Propertized(s, _) => visitor.visit_byte_buf(s.into_bytes()),
+            Value::Unibyte(bytes) => visitor.visit_byte_buf(bytes),
+            _ => Err(self.invalid_type(&"a string")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Nil => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Nil => visitor.visit_unit(),
+            _ => Err(self.invalid_type(&"nil")),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match into_items(self.value) {
+            Ok(items) => visit_seq(items, visitor),
+            Err(value) => Err(Deserializer::new(value).invalid_type(&"a list or a vector")),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match into_pairs(self.value) {
+            Ok(pairs) => visit_map(pairs, visitor),
+            Err(value) => {
+                Err(Deserializer::new(value).invalid_type(&"an alist, a plist or a hash table"))
+            }
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        match self.value {
+            Value::List(ref items) if is_alist(items) || is_plist(items) => {
+                self.deserialize_map(visitor)
+            }
+            Value::HashTable { .. } | Value::Nil => self.deserialize_map(visitor),
+            // a record starts with its type, other items are slots
+            Value::Record(ref items) if items.is_empty() => {
+                Err(self.invalid_type(&"a record with a type"))
+            }
+            Value::Record(items) => visit_seq(items.into_iter().skip(1).collect(), visitor),
+            _ => self.deserialize_seq(visitor),
+        }
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let (variant, rest, dotted) = match self.value {
+            Value::Symbol(_) | Value::String(_) => (self.value, Vec::new(), false),
+            Value::List(ref items) if items.is_empty() => {
+                return Err(self.invalid_type(&"a symbol or a list"))
+            }
+            Value::List(items) => {
+                let mut items = items.into_iter();
+                let variant = items.next().unwrap_or(Value::Nil);
+                (variant, items.collect(), false)
+            }
+            Value::Cons(car, cdr) => (*car, vec![*cdr], true),
+            _ => return Err(self.invalid_type(&"a symbol or a list")),
+        };
+        visitor.visit_enum(EnumDeserializer {
+            variant,
+            rest,
+            dotted,
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            Value::Symbol(s) => match s.strip_prefix(':') {
+                Some(name) => visitor.visit_str(name),
+                None => visitor.visit_string(s),
+            },
+            _ => self.deserialize_string(visitor),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64
+    }
+}
+
+fn visit_seq<'de, V: Visitor<'de>>(items: Vec<Value>, visitor: V) -> Result<V::Value> {
+    let len = items.len();
+    let mut seq = SeqDeserializer {
+        iter: items.into_iter(),
+    };
+    let res = visitor.visit_seq(&mut seq)?;
+    if seq.iter.len() != 0 {
+        return Err(de::Error::invalid_length(len, &"fewer elements"));
+    }
+    Ok(res)
+}
+
+fn visit_map<'de, V: Visitor<'de>>(pairs: Vec<(Value, Value)>, visitor: V) -> Result<V::Value> {
+    let mut map = MapDeserializer {
+        iter: pairs.into_iter(),
+        value: None,
+    };
+    visitor.visit_map(&mut map)
+}
+
+struct SeqDeserializer {
+    iter: std::vec::IntoIter<Value>,
+}
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(Deserializer::new(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: std::vec::IntoIter<(Value, Value)>,
+    value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Deserializer::new(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| <Error as de::Error>::custom("value is missing"))?;
+        seed.deserialize(Deserializer::new(value))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// An enum variant is either a symbol (a unit variant) or a list which starts
+/// with the name of a variant: `(circle 1.0)`, `(point 1 2)` or
+/// `(person :name "Isaac")`.
+struct EnumDeserializer {
+    variant: Value,
+    rest: Vec<Value>,
+    /// true for `(variant . value)`
+    dotted: bool,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(mut self, seed: V) -> Result<(V::Value, Self)> {
+        let variant = std::mem::replace(&mut self.variant, Value::Nil);
+        let name = seed.deserialize(Deserializer::new(variant))?;
+        Ok((name, self))
+    }
+}
+
+impl EnumDeserializer {
+    /// Return the value of a tuple or a struct variant.
+    fn into_value(mut self) -> Value {
+        if self.dotted {
+            self.rest.remove(0)
+        } else {
+            Value::list(self.rest)
+        }
+    }
+}
+
+impl<'de> VariantAccess<'de> for EnumDeserializer {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        if self.rest.is_empty() {
+            Ok(())
+        } else {
+            Err(de::Error::invalid_length(
+                self.rest.len(),
+                &"a unit variant",
+            ))
+        }
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(mut self, seed: T) -> Result<T::Value> {
+        if self.rest.len() != 1 {
+            return Err(de::Error::invalid_length(
+                self.rest.len(),
+                &"a newtype variant",
+            ));
+        }
+        seed.deserialize(Deserializer::new(self.rest.remove(0)))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(Deserializer::new(self.into_value()), visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_struct(
+            Deserializer::new(self.into_value()),
+            "",
+            fields,
+            visitor,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Node {
+        id: String,
+        level: usize,
+        tags: Vec<String>,
+        todo: Option<String>,
+    }
+
+    #[test]
+    fn test_struct_from_plist() {
+        let node: Node = from_str(r#"(:id "1" :level 2 :tags ("physics") :todo nil)"#).unwrap();
+        assert_eq!(
+            node,
+            Node {
+                id: "1".into(),
+                level: 2,
+                tags: vec!["physics".into()],
+                todo: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_struct_from_vector() {
+        let node: Node = from_str(r#"["1" 2 nil "TODO"]"#).unwrap();
+        assert_eq!(node.tags, Vec::<String>::new());
+        assert_eq!(node.todo.as_deref(), Some("TODO"));
+    }
+
+    #[test]
+    fn test_properties_alist() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "UPPERCASE")]
+        struct Properties {
+            id: String,
+            category: String,
+            #[serde(default)]
+            priority: Option<String>,
+        }
+
+        let src = r#"(("CATEGORY" . "physics") ("ID" . "1") ("BLOCKED" . ""))"#;
+        let props: Properties = from_str(src).unwrap();
+        assert_eq!(
+            props,
+            Properties {
+                id: "1".into(),
+                category: "physics".into(),
+                priority: None,
+            }
+        );
+
+        let map: HashMap<String, String> = from_str(src).unwrap();
+        assert_eq!(map["BLOCKED"], "");
+
+        // lists of even length aren't plists without symbol keys
+        let plist: HashMap<String, i64> = from_str("(:a 1 b 2)").unwrap();
+        assert_eq!(plist.len(), 2);
+        assert!(from_str::<HashMap<String, String>>(r#"("A" "B")"#).is_err());
+        assert!(from_str::<HashMap<String, i64>>("(1 2 3 4)").is_err());
+    }
+
+    #[test]
+    fn test_map_from_hash_table() {
+        let src = r#"#s(hash-table test equal data ("a" 1 "b" 2))"#;
+        let map: HashMap<String, i64> = from_str(src).unwrap();
+        assert_eq!(map, HashMap::from([("a".into(), 1), ("b".into(), 2)]));
+    }
+
+    #[test]
+    fn test_tuples_and_conses() {
+        let pair: (String, f64) = from_str(r#"("ID" . 1.5)"#).unwrap();
+        assert_eq!(pair, ("ID".into(), 1.5));
+        let triple: (i64, i64, i64) = from_str("(1 2 . 3)").unwrap();
+        assert_eq!(triple, (1, 2, 3));
+        assert!(from_str::<(i64, i64)>("(1 2 3)").is_err());
+    }
+
+    #[test]
+    fn test_enums() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "kebab-case")]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Point(i64, i64),
+            Named { name: String },
+        }
+
+        let shapes: Vec<Shape> =
+            from_str(r#"(empty :empty (circle 1.0) (point 1 2) (named :name "x"))"#).unwrap();
+        assert_eq!(
+            shapes,
+            vec![
+                Shape::Empty,
+                Shape::Empty,
+                Shape::Circle(1.0),
+                Shape::Point(1, 2),
+                Shape::Named { name: "x".into() },
+            ]
+        );
+        // the reader never builds an empty list, but it can be built by hand
+        assert!(from_value::<Shape>(Value::List(Vec::new())).is_err());
+    }
+
+    #[test]
+    fn test_empty_records_and_alist_items() {
+        assert!(from_value::<Node>(Value::Record(Vec::new())).is_err());
+        let node: Node = from_str(r#"#s(node "1" 2 nil nil)"#).unwrap();
+        assert_eq!(node.level, 2);
+
+        // an empty alist item has a `nil` key, which isn't a string
+        let alist = Value::list(vec![Value::List(Vec::new())]);
+        assert!(from_value::<HashMap<String, Option<i64>>>(alist).is_err());
+    }
+
+    #[test]
+    fn test_bool_and_errors() {
+        assert_eq!(from_str::<Vec<bool>>("(t nil)"), Ok(vec![true, false]));
+        assert!(from_str::<bool>("1").is_err());
+        assert!(from_str::<String>("1").is_err());
+        assert!(from_str::<u8>("300").is_err());
+        let err = from_str::<Vec<bool>>("(t").unwrap_err();
+        assert!(
+            err.to_string().starts_with("unbalanced parentheses"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_serde_column() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Props {
+            #[serde(rename = "ID")]
+            id: String,
+        }
+
+        let props = Serde::<Props>::from_lisp(lisp::parse_string(r#"(("ID" . "x"))"#).unwrap());
+        assert_eq!(props.unwrap().0, Props { id: "x".into() });
+    }
+}
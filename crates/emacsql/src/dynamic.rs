@@ -0,0 +1,142 @@
+//! Rows of queries which aren't known beforehand, like the ones a user types.
+
+use std::sync::Arc;
+
+use rusqlite::{Params, Statement};
+
+use crate::lisp;
+use crate::prelude::Result;
+use crate::row::Row;
+use crate::value::Value;
+
+/// A row with the names of its columns and their decoded values.
+///
+/// The names are shared by all rows of a query.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DynamicRow {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl DynamicRow {
+    /// Run a statement and decode every column of every row.
+    pub fn query<P: Params>(stmt: &mut Statement, params: P) -> Result<Vec<Self>> {
+        let columns = column_names(stmt);
+        let mut rows = stmt.query(params)?;
+        let mut decoded = Vec::new();
+        while let Some(row) = rows.next()? {
+            decoded.push(Self::from_row(&Row::from(row), columns.clone())?);
+        }
+        Ok(decoded)
+    }
+
+    /// Decode every column of a row, `columns` are the names of the columns
+    /// of its statement, see [`column_names`].
+    pub fn from_row(row: &Row, columns: Arc<[String]>) -> Result<Self> {
+        let values = (0..columns.len())
+            .map(|idx| row.get(idx))
+            .collect::<Result<_>>()?;
+        Ok(Self { columns, values })
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    pub fn into_values(self) -> Vec<Value> {
+        self.values
+    }
+
+    /// Return the value of a column, names are compared ignoring ASCII case
+    /// like SQLite does.
+    pub fn get(&self, column: &str) -> Option<&Value> {
+        let idx = self
+            .columns
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(column))?;
+        self.values.get(idx)
+    }
+
+    /// Iterate over the names of columns and their values.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Value)> {
+        self.columns.iter().map(String::as_str).zip(&self.values)
+    }
+
+    /// Return the row as a plist like `(:id "1" :title "Physics")`.
+    pub fn to_sexp(&self) -> lisp::Value {
+        let plist = self.iter().flat_map(|(column, value)| {
+            [
+                lisp::Value::Symbol(format!(":{column}")),
+                value.clone().into(),
+            ]
+        });
+        lisp::Value::list(plist.collect())
+    }
+
+    /// Return the row as a JSON object like `{"id": "1", "title": "Physics"}`,
+    /// values are converted according to `options`.
+    #[cfg(feature = "json")]
+    pub fn to_json(&self, options: &crate::json::JsonOptions) -> serde_json::Value {
+        let entries = self
+            .iter()
+            .map(|(column, value)| (column.to_string(), options.to_json(&value.clone().into())));
+        serde_json::Value::Object(entries.collect())
+    }
+}
+
+/// Return the names of the columns of a statement, to be shared by its rows.
+pub fn column_names(stmt: &Statement) -> Arc<[String]> {
+    stmt.column_names().into_iter().map(String::from).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dynamic_rows() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id, title, level, olp, properties);
+INSERT INTO nodes VALUES
+  ('"1"', '"Physics"', 0, NULL, '#s(hash-table data (done t))'),
+  ('"2"', '"say \"hi\""', 1, '("Physics")', '(("ID" . "2"))');
+"#,
+        )
+        .unwrap();
+        let mut stmt = conn.prepare("SELECT * FROM nodes ORDER BY id").unwrap();
+        let rows = DynamicRow::query(&mut stmt, []).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            rows[0].columns(),
+            ["id", "title", "level", "olp", "properties"]
+        );
+        assert_eq!(rows[1].get("LEVEL"), Some(&Value::Integer(1)));
+        assert_eq!(rows[1].get("todo"), None);
+
+        #[cfg(feature = "json")]
+        {
+            use crate::json::JsonOptions;
+            use serde_json::json;
+
+            let options = JsonOptions::default();
+            assert_eq!(
+                rows[0].to_json(&options),
+                json!({"id": "1", "title": "Physics", "level": 0, "olp": null, "properties": {"done": true}})
+            );
+            assert_eq!(
+                rows[1].to_json(&options),
+                json!({"id": "2", "title": "say \"hi\"", "level": 1, "olp": ["Physics"], "properties": {"ID": "2"}})
+            );
+        }
+        assert_eq!(
+            rows[1].to_sexp().to_string(),
+            r#"(:id "2" :title "say \"hi\"" :level 1 :olp ("Physics") :properties (("ID" . "2")))"#
+        );
+    }
+}
@@ -1,3 +1,5 @@
+use std::fmt::{self, Display};
+
 use rusqlite;
 
 use crate::lisp;
@@ -6,7 +8,98 @@ pub type Error = rusqlite::Error;
 pub type FromEmacsqlError = rusqlite::types::FromSqlError;
 
 impl From<lisp::Error> for FromEmacsqlError {
-    fn from(_error: lisp::Error) -> Self {
-        FromEmacsqlError::InvalidType
+    fn from(error: lisp::Error) -> Self {
+        FromEmacsqlError::Other(Box::new(error))
+    }
+}
+
+/// An error of decoding a column of a row.
+///
+/// [`crate::Row::get`] returns it inside of
+/// [`rusqlite::Error::FromSqlConversionFailure`], so it can be found with
+/// `downcast_ref`.
+#[derive(Debug)]
+pub struct DecodeError {
+    /// the table of the column, when the query reads only one table
+    pub table: Option<String>,
+    pub column: String,
+    /// the raw text stored in the column, if it was a text
+    pub text: Option<String>,
+    pub source: FromEmacsqlError,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "can't decode column `{}`", self.column)?;
+        if let Some(table) = &self.table {
+            write!(f, " of table `{table}`")?;
+        }
+        if let Some(text) = &self.text {
+            write!(f, " with text {text:?}")?;
+        }
+        write!(f, ": {}", self.source)
+    }
+}
+
+impl std::error::Error for DecodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Row;
+
+    fn get_title(sql: &str) -> Error {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id, title);
+CREATE TABLE tags (node_id, tag);
+INSERT INTO nodes VALUES ('"1"', '"broken');
+INSERT INTO tags VALUES ('"1"', '"tag"');
+"#,
+        )
+        .unwrap();
+        conn.query_row(sql, [], |row| Row::from(row).get::<_, String>("title"))
+            .unwrap_err()
+    }
+
+    fn decode_error(err: &Error) -> &DecodeError {
+        match err {
+            Error::FromSqlConversionFailure(_, _, err) => err.downcast_ref().unwrap(),
+            _ => panic!("not a decode error: {err:?}"),
+        }
+    }
+
+    #[test]
+    fn test_decode_error() {
+        let err = get_title("SELECT id, title FROM nodes WHERE id = '\"1\"'");
+        let decode = decode_error(&err);
+        assert_eq!(decode.table.as_deref(), Some("nodes"));
+        assert_eq!(decode.column, "title");
+        assert_eq!(decode.text.as_deref(), Some("\"broken"));
+        assert_eq!(
+            decode.to_string(),
+            "can't decode column `title` of table `nodes` with text \"\\\"broken\": \
+             unexpected end of input at line 1, column 8 (byte 7) near `\"broken`"
+        );
+    }
+
+    #[test]
+    fn test_decode_error_of_join() {
+        let err = get_title("SELECT title FROM nodes JOIN tags ON id = node_id");
+        let decode = decode_error(&err);
+        assert_eq!(decode.table, None);
+        assert_eq!(decode.column, "title");
+    }
+
+    #[test]
+    fn test_decode_error_of_union() {
+        let err =
+            get_title("SELECT tag AS title FROM tags UNION ALL SELECT title FROM nodes ORDER BY 1");
+        assert_eq!(decode_error(&err).table, None);
     }
 }
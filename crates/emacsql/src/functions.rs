@@ -0,0 +1,245 @@
+//! SQL functions which read EmacSQL values, so queries can match decoded
+//! values instead of their printed text.
+//!
+//! After [`register_functions`] a connection has:
+//!
+//! - `lisp_string(x)`, the text of a string or the name of a symbol, `NULL`
+//!   for anything else: `WHERE lisp_string(title) = 'Physics'`;
+//! - `lisp_car(x)`, the first item of a list;
+//! - `plist_get(x, prop)` and `alist_get(x, key)`, the value of a key of a
+//!   plist or an alist, the key is lisp text like `':outline'` or
+//!   `'"CATEGORY"'`;
+//! - `lisp_like(x, pattern)`, whether the text of a string or a symbol
+//!   matches a pattern of `LIKE`.
+//!
+//! `lisp_car`, `plist_get` and `alist_get` return values stored like EmacSQL
+//! stores them, so calls can be nested: `lisp_string(lisp_car(olp))`.  A value
+//! which can't be read is `nil`, and a `NULL` or unreadable key or pattern
+//! gives `NULL`, instead of failing the whole query.
+
+use rusqlite::functions::{Context, FunctionFlags};
+use rusqlite::Connection;
+
+use crate::lisp;
+use crate::value::{FromEmacsql, InvalidText, Value};
+
+/// Register the functions on a connection.
+pub fn register_functions(conn: &Connection) -> rusqlite::Result<()> {
+    conn.create_scalar_function("lisp_string", 1, flags(), |ctx| Ok(text(arg(ctx, 0))))?;
+    conn.create_scalar_function("lisp_car", 1, flags(), |ctx| {
+        Ok(stored(arg(ctx, 0).car().cloned()))
+    })?;
+    conn.create_scalar_function("plist_get", 2, flags(), |ctx| {
+        let Some(prop) = key(ctx, 1) else {
+            return Ok(Value::Null);
+        };
+        Ok(stored(arg(ctx, 0).plist_get(&prop).cloned()))
+    })?;
+    conn.create_scalar_function("alist_get", 2, flags(), |ctx| {
+        let Some(key) = key(ctx, 1) else {
+            return Ok(Value::Null);
+        };
+        Ok(stored(arg(ctx, 0).alist_get(&key).map(lisp::Cdr::to_value)))
+    })?;
+    conn.create_scalar_function("lisp_like", 2, flags(), |ctx| {
+        // `x LIKE NULL` is `NULL` too
+        let Some(pattern) = ctx.get::<Option<String>>(1)? else {
+            return Ok(None);
+        };
+        Ok(text(arg(ctx, 0)).map(|text| like(&text, &pattern)))
+    })
+}
+
+fn flags() -> FunctionFlags {
+    FunctionFlags::SQLITE_UTF8 | FunctionFlags::SQLITE_DETERMINISTIC
+}
+
+/// Read an argument like a column of EmacSQL, `nil` if it can't be read.
+fn arg(ctx: &Context, idx: usize) -> lisp::Value {
+    Value::from_sql_with(ctx.get_raw(idx), InvalidText::Raw)
+        .and_then(lisp::Value::from_emacsql)
+        .unwrap_or(lisp::Value::Nil)
+}
+
+/// Read an argument which is lisp text written in a query, like `':outline'`,
+/// `None` if it's `NULL` or can't be read.
+fn key(ctx: &Context, idx: usize) -> Option<lisp::Value> {
+    let src: String = ctx.get(idx).ok()?;
+    lisp::parse_string(&src).ok()
+}
+
+fn text(value: lisp::Value) -> Option<String> {
+    match value {
+        lisp::Value::String(s) | lisp::Value::Propertized(s, _) | lisp::Value::Symbol(s) => Some(s),
+        _ => None,
+    }
+}
+
+/// Return a value as EmacSQL stores it, `None` is `NULL`.
+fn stored(value: Option<lisp::Value>) -> Value {
+    value.map_or(Value::Null, Value::Lisp)
+}
+
+/// Match `text` against a pattern of `LIKE`: `%` is any text, `_` is any
+/// character and ASCII letters are compared ignoring case.
+fn like(text: &str, pattern: &str) -> bool {
+    let text: Vec<char> = text.chars().collect();
+    let pattern: Vec<char> = pattern.chars().collect();
+    let (mut t, mut p) = (0, 0);
+    // where the last `%` is and the text position it matches up to
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(&ch) if ch == '_' || ch.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((bp, bt)) => {
+                    p = bp;
+                    t = bt + 1;
+                    backtrack = Some((bp, bt + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&ch| ch == '%')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        register_functions(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id, title, olp, properties, level);
+INSERT INTO nodes VALUES
+  ('"1"', '"Physics"', NULL, '(("CATEGORY" . "science"))', 0),
+  ('"2"', '"Momentum"', '("Physics" "Mechanics")', '(("CATEGORY" . "physics"))', 2);
+CREATE TABLE links (source, properties);
+INSERT INTO links VALUES ('"2"', '(:outline ("Physics") :pos 10)');
+"#,
+        )
+        .unwrap();
+        conn
+    }
+
+    fn query(conn: &Connection, sql: &str) -> Vec<Option<String>> {
+        conn.prepare(sql)
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_lisp_string_and_car() {
+        let conn = conn();
+        assert_eq!(
+            query(
+                &conn,
+                "SELECT lisp_string(id) FROM nodes WHERE lisp_string(title) = 'Momentum'"
+            ),
+            [Some("2".into())]
+        );
+        assert_eq!(
+            query(&conn, "SELECT lisp_string(lisp_car(olp)) FROM nodes"),
+            [None, Some("Physics".into())]
+        );
+        assert_eq!(
+            query(&conn, "SELECT lisp_string(level) FROM nodes"),
+            [None, None]
+        );
+    }
+
+    #[test]
+    fn test_plist_and_alist_get() {
+        let conn = conn();
+        assert_eq!(
+            query(
+                &conn,
+                r#"SELECT lisp_string(alist_get(properties, '"CATEGORY"')) FROM nodes"#
+            ),
+            [Some("science".into()), Some("physics".into())]
+        );
+        assert_eq!(
+            query(&conn, "SELECT plist_get(properties, ':outline') FROM links"),
+            [Some(r#"("Physics")"#.into())]
+        );
+        let pos: i64 = conn
+            .query_row(
+                "SELECT plist_get(properties, ':pos') FROM links",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(pos, 10);
+        // a `NULL` or unreadable key finds nothing
+        for key in ["NULL", "'('"] {
+            assert_eq!(
+                query(
+                    &conn,
+                    &format!("SELECT plist_get(properties, {key}) FROM links")
+                ),
+                [None]
+            );
+            assert_eq!(
+                query(
+                    &conn,
+                    &format!("SELECT alist_get(properties, {key}) FROM nodes")
+                ),
+                [None, None]
+            );
+        }
+    }
+
+    #[test]
+    fn test_lisp_like() {
+        let conn = conn();
+        assert_eq!(
+            query(
+                &conn,
+                "SELECT lisp_string(title) FROM nodes WHERE lisp_like(title, 'mom%')"
+            ),
+            [Some("Momentum".into())]
+        );
+        assert!(like("Physics", "%s_c%"));
+        assert!(like("", "%"));
+        assert!(!like("Physics", "phys"));
+        assert!(!like("abc", "a%d"));
+    }
+
+    #[test]
+    fn test_null_and_unreadable_args() {
+        let conn = conn();
+        conn.execute_batch(r#"INSERT INTO nodes VALUES ('"3"', '"broken', '(a', 'b)', 1)"#)
+            .unwrap();
+        assert_eq!(
+            query(&conn, "SELECT lisp_like(title, NULL) FROM nodes"),
+            [None, None, None]
+        );
+        assert_eq!(
+            query(
+                &conn,
+                r#"SELECT lisp_string(title) || lisp_string(lisp_car(olp)) || lisp_string(alist_get(properties, '"CATEGORY"')) FROM nodes WHERE id = '"3"'"#
+            ),
+            [None]
+        );
+        assert_eq!(
+            query(
+                &conn,
+                "SELECT lisp_string(id) FROM nodes WHERE NOT lisp_like(title, '%s')"
+            ),
+            [Some("2".into())]
+        );
+    }
+}
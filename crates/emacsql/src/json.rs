@@ -0,0 +1,296 @@
+//! Conversion between lisp values and [`serde_json::Value`].
+//!
+//! With the default [`JsonOptions`] a lisp value is written as JSON like:
+//!
+//! | lisp                           | JSON                      |
+//! |--------------------------------|---------------------------|
+//! | `nil`                          | `null`                    |
+//! | `t`                            | `true`                    |
+//! | `42`, `1.5`                    | `42`, `1.5`               |
+//! | a bignum, `1.0e+INF`           | `"123…"`, `null`          |
+//! | `"text"`, `foo`, `:foo`        | `"text"`, `"foo"`, `":foo"` |
+//! | `(1 2)`, `[1 2]`, `#s(a 1)`    | `[1, 2]`, `[1, 2]`, `["a", 1]` |
+//! | `(1 . 2)`, `(1 2 . 3)`         | `[1, 2]`, `[1, [2, 3]]`   |
+//! | `(("ID" . "1") (tags "a"))`    | `{"ID": "1", "tags": ["a"]}` |
+//! | a hash table                   | an object                 |
+//!
+//! JSON is read back the same way: `null` and `false` are `nil`, arrays are
+//! lists and objects are alists with string keys.  So symbols, vectors and
+//! conses come back as strings and lists, unless [`JsonOptions::symbols`] and
+//! [`JsonOptions::conses`] are [`Style::Tagged`].
+
+use serde_json::{Map, Number};
+
+use crate::lisp::{is_alist, is_plist, Value};
+
+/// How values which JSON doesn't have are written.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum Style {
+    /// like the closest JSON value, a symbol is a string and a cons is an
+    /// array of its car and cdr, so the cdr of `(1 2 . 3)` is nested
+    #[default]
+    Plain,
+    /// as an object with one key, `{"symbol": "foo"}` or
+    /// `{"cons": [car, cdr]}`, which is read back as the same value
+    Tagged,
+}
+
+/// The rules of the conversion, see [the module](self).
+#[derive(Debug, PartialEq, Clone)]
+pub struct JsonOptions {
+    /// the JSON value of `nil`
+    pub nil: serde_json::Value,
+    /// the JSON value of `t`
+    pub t: serde_json::Value,
+    pub symbols: Style,
+    pub conses: Style,
+    /// write alists whose keys are strings or symbols as objects
+    pub alists_as_objects: bool,
+    /// write plists like `(:id 1 :title "A")` as objects `{"id": 1, "title": "A"}`
+    pub plists_as_objects: bool,
+}
+
+impl Default for JsonOptions {
+    fn default() -> Self {
+        Self {
+            nil: serde_json::Value::Null,
+            t: serde_json::Value::Bool(true),
+            symbols: Style::Plain,
+            conses: Style::Plain,
+            alists_as_objects: true,
+            plists_as_objects: false,
+        }
+    }
+}
+
+impl JsonOptions {
+    /// Options which read JSON back as almost the same lisp value: text
+    /// properties are lost, bignums and unibyte strings become strings,
+    /// vectors and records become lists, and hash tables become alists.
+    pub fn tagged() -> Self {
+        Self {
+            symbols: Style::Tagged,
+            conses: Style::Tagged,
+            alists_as_objects: false,
+            ..Self::default()
+        }
+    }
+
+    pub fn to_json(&self, value: &Value) -> serde_json::Value {
+        let array = |items: &[Value]| items.iter().map(|item| self.to_json(item)).collect();
+        match value {
+            Value::Nil => self.nil.clone(),
+            Value::Symbol(s) if s == "t" => self.t.clone(),
+            Value::Symbol(s) => match self.symbols {
+                Style::Plain => s.as_str().into(),
+                Style::Tagged => tagged("symbol", s.as_str().into()),
+            },
+            Value::String(s) | Value::Propertized(s, _) => s.as_str().into(),
+            Value::Unibyte(bytes) => String::from_utf8_lossy(bytes).into(),
+            Value::Integer(n) => (*n).into(),
+            Value::BigInt(n) => n.as_str().into(),
+            // `from_f64` is `None` for infinities and NaN
+            Value::Real(n) => Number::from_f64(*n).map_or(serde_json::Value::Null, Into::into),
+            Value::List(items) => match self.object_entries(items) {
+                Some(entries) => serde_json::Value::Object(entries),
+                None => serde_json::Value::Array(array(items)),
+            },
+            Value::Vector(items) | Value::Record(items) => serde_json::Value::Array(array(items)),
+            Value::Cons(car, cdr) => {
+                let pair = vec![self.to_json(car), self.to_json(cdr)];
+                match self.conses {
+                    Style::Plain => serde_json::Value::Array(pair),
+                    Style::Tagged => tagged("cons", serde_json::Value::Array(pair)),
+                }
+            }
+            Value::HashTable { data, .. } => serde_json::Value::Object(
+                data.iter()
+                    .map(|(key, value)| (object_key(key), self.to_json(value)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Return the entries of an alist or a plist, if they're written as an
+    /// object.
+    fn object_entries(&self, items: &[Value]) -> Option<Map<String, serde_json::Value>> {
+        let name = |key: &Value| match key {
+            Value::String(s) | Value::Symbol(s) => Some(s.clone()),
+            _ => None,
+        };
+        let entries = if self.alists_as_objects && is_alist(items) {
+            items
+                .iter()
+                .map(|item| {
+                    let value = item.cdr()?.to_value();
+                    Some((name(item.car()?)?, self.to_json(&value)))
+                })
+                .collect::<Option<Vec<_>>>()?
+        } else if self.plists_as_objects && is_plist(items) {
+            items
+                .chunks(2)
+                .map(|pair| {
+                    let key = name(&pair[0])?;
+                    let key = key.strip_prefix(':').map_or(key.clone(), str::to_string);
+                    Some((key, self.to_json(&pair[1])))
+                })
+                .collect::<Option<Vec<_>>>()?
+        } else {
+            return None;
+        };
+        let len = entries.len();
+        let map: Map<_, _> = entries.into_iter().collect();
+        // an object can't have the same key twice
+        (map.len() == len).then_some(map)
+    }
+
+    pub fn to_lisp(&self, json: &serde_json::Value) -> Value {
+        if *json == self.nil {
+            return Value::Nil;
+        }
+        if *json == self.t {
+            return Value::Symbol("t".into());
+        }
+        match json {
+            serde_json::Value::Null | serde_json::Value::Bool(false) => Value::Nil,
+            serde_json::Value::Bool(true) => Value::Symbol("t".into()),
+            serde_json::Value::Number(n) => match n.as_i64() {
+                Some(n) => Value::Integer(n),
+                // an integer which doesn't fit into `i64`
+                None if n.is_u64() => Value::BigInt(n.to_string()),
+                None => Value::Real(n.as_f64().unwrap_or(f64::NAN)),
+            },
+            serde_json::Value::String(s) => Value::String(s.clone()),
+            serde_json::Value::Array(items) => {
+                Value::list(items.iter().map(|item| self.to_lisp(item)).collect())
+            }
+            serde_json::Value::Object(map) => self.object_to_lisp(map),
+        }
+    }
+
+    fn object_to_lisp(&self, map: &Map<String, serde_json::Value>) -> Value {
+        let mut entries = map.iter();
+        match (entries.next(), entries.next()) {
+            (Some((key, serde_json::Value::String(name))), None)
+                if key == "symbol" && self.symbols == Style::Tagged =>
+            {
+                return Value::Symbol(name.clone());
+            }
+            (Some((key, serde_json::Value::Array(pair))), None)
+                if key == "cons" && self.conses == Style::Tagged && pair.len() == 2 =>
+            {
+                let (car, cdr) = (self.to_lisp(&pair[0]), self.to_lisp(&pair[1]));
+                return Value::Cons(Box::new(car), Box::new(cdr));
+            }
+            _ => {}
+        }
+        let alist = map.iter().map(|(key, value)| {
+            Value::dotted_list(vec![Value::String(key.clone())], self.to_lisp(value))
+        });
+        Value::list(alist.collect())
+    }
+}
+
+fn tagged(tag: &str, value: serde_json::Value) -> serde_json::Value {
+    serde_json::Value::Object(Map::from_iter([(tag.to_string(), value)]))
+}
+
+/// Return the key of an object for a key of a hash table.
+fn object_key(key: &Value) -> String {
+    match key {
+        Value::String(s) | Value::Symbol(s) => s.clone(),
+        key => key.to_string(),
+    }
+}
+
+/// Convert with the default [`JsonOptions`].
+impl From<&Value> for serde_json::Value {
+    fn from(value: &Value) -> Self {
+        JsonOptions::default().to_json(value)
+    }
+}
+
+/// Convert with the default [`JsonOptions`].
+impl From<&serde_json::Value> for Value {
+    fn from(json: &serde_json::Value) -> Self {
+        JsonOptions::default().to_lisp(json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn lisp(src: &str) -> Value {
+        src.parse().unwrap()
+    }
+
+    #[test]
+    fn test_to_json() {
+        let options = JsonOptions::default();
+        let node = lisp(
+            r#"(:id "1" :todo nil :done t :level 2 :pos 1.5 :olp ("A" "B")
+               :properties (("ID" . "1") ("TAGS" "a" "b")) :pair (1 . b) :big 18446744073709551616)"#,
+        );
+        assert_eq!(
+            options.to_json(&node),
+            json!([
+                ":id", "1", ":todo", null, ":done", true, ":level", 2, ":pos", 1.5,
+                ":olp", ["A", "B"], ":properties", {"ID": "1", "TAGS": ["a", "b"]},
+                ":pair", [1, "b"], ":big", "18446744073709551616"
+            ])
+        );
+        let options = JsonOptions {
+            nil: json!(false),
+            t: json!("t"),
+            plists_as_objects: true,
+            ..JsonOptions::default()
+        };
+        assert_eq!(
+            options.to_json(&lisp(
+                r#"(:todo nil :done t :refs #s(hash-table data ("cite" [k1])))"#
+            )),
+            json!({"todo": false, "done": "t", "refs": {"cite": ["k1"]}})
+        );
+        // an improper list nests its tail like the conses it's made of
+        assert_eq!(options.to_json(&lisp("(1 2 . 3)")), json!([1, [2, 3]]));
+        // an alist with the same key twice isn't an object
+        assert_eq!(
+            serde_json::Value::from(&lisp("((a . 1) (a . 2))")),
+            json!([["a", 1], ["a", 2]])
+        );
+        assert_eq!(serde_json::Value::from(&lisp("1.0e+INF")), json!(null));
+    }
+
+    #[test]
+    fn test_to_lisp() {
+        let json = json!({"ID": "1", "TAGS": ["a", "b"], "done": true, "todo": null, "n": 1.5});
+        assert_eq!(
+            Value::from(&json).to_string(),
+            r#"(("ID" . "1") ("TAGS" "a" "b") ("done" . t) ("n" . 1.5) ("todo"))"#
+        );
+        assert_eq!(
+            Value::from(&json!(18446744073709551615u64)),
+            Value::BigInt("18446744073709551615".into())
+        );
+        assert_eq!(Value::from(&json!([false, -1])), lisp("(nil -1)"));
+        // tags are read only with the tagged style
+        assert_eq!(
+            Value::from(&json!({"symbol": "foo"})),
+            lisp(r#"(("symbol" . "foo"))"#)
+        );
+    }
+
+    #[test]
+    fn test_tagged_round_trip() {
+        let options = JsonOptions::tagged();
+        let value =
+            lisp(r#"(:title "A" :olp ("x" y) :props (("ID" . "1") (k . nil)) :dotted (1 2 . 3))"#);
+        let json = options.to_json(&value);
+        assert_eq!(json[0], json!({"symbol": ":title"}));
+        assert_eq!(json[5][0], json!({"cons": ["ID", "1"]}));
+        assert_eq!(options.to_lisp(&json), value);
+    }
+}
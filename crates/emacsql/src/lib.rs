@@ -1,15 +1,37 @@
 extern crate rusqlite;
+// lets the code generated by `#[derive(FromRow)]` refer to `::emacsql` here too
+extern crate self as emacsql;
+pub mod compiler;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod dynamic;
 pub mod error;
+#[cfg(feature = "functions")]
+pub mod functions;
+#[cfg(feature = "json")]
+pub mod json;
 pub mod lisp;
 pub mod params;
 pub mod prelude;
 pub mod query;
 pub mod row;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod time;
 mod utils;
 pub mod value;
 
-pub use error::Error;
+#[cfg(feature = "serde")]
+pub use de::Serde;
+pub use dynamic::DynamicRow;
+#[cfg(feature = "derive")]
+pub use emacsql_derive::FromRow;
+pub use error::{DecodeError, Error};
+#[cfg(feature = "functions")]
+pub use functions::register_functions;
+pub use params::{ToEmacsql, ToLisp};
 pub use prelude::*;
-pub use query::QueryAs;
-pub use row::{FromRow, Row};
+pub use query::{QueryAs, QueryTyped, RowsAs};
+pub use row::{FromRow, OwnedRow, Row};
 pub use value::Value;
@@ -1,18 +1,98 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write};
+use std::num::IntErrorKind;
 use std::str::FromStr;
 
+mod access;
+mod build;
+mod printer;
+mod reader;
+mod value_ref;
+
+pub use access::{Cdr, Iter};
+pub use build::IntoIter;
+#[doc(hidden)]
+pub use build::SexpLiteral;
+pub use printer::to_lisp_string;
+pub use reader::{read_all, Form, Forms, Position, ReadError, Reader};
+pub use value_ref::ValueRef;
+
+// Lists are represented like Emacs sees them: `nil` and `()` are the same
+// `Nil`, a proper list is a `List` and an improper list like `(1 2 . 3)` is a
+// chain of `Cons` cells: `Cons(1, Cons(2, 3))`.  Use `Value::cons` to build
+// them, the cdr of a `Cons` is never a `List` or `Nil`.
 #[derive(PartialEq, Clone, Debug)]
 pub enum Value {
     String(String),
     Cons(Box<Value>, Box<Value>),
     Integer(i64),
+    /// an integer which doesn't fit into `i64`, as decimal text like
+    /// `-123456789012345678901234`
+    BigInt(String),
     Real(f64),
     Symbol(String),
-    // emacslisp contains both: List and Vector, I parse it as the same things
+    /// a proper non-empty list: `(1 2 3)`
     List(Vec<Value>),
+    /// a vector: `[1 2 3]`
+    Vector(Vec<Value>),
     Nil,
+    /// `#s(hash-table test equal data (k1 v1 k2 v2))`, `test` is the name of
+    /// the function which compares keys
+    HashTable {
+        test: Option<String>,
+        data: Vec<(Value, Value)>,
+    },
+    /// `#s(name slot1 slot2)`, the first item is the type of a record
+    Record(Vec<Value>),
+    /// `#("text" 0 4 (face bold))`, a string with text properties
+    Propertized(String, Vec<TextProperty>),
+    /// `"\377"`, a unibyte string with raw bytes, which aren't always UTF-8
+    Unibyte(Vec<u8>),
+}
+
+/// Properties of the characters from `start` to `end` (exclusive) of a
+/// propertized string.
+#[derive(PartialEq, Clone, Debug)]
+pub struct TextProperty<V = Value> {
+    pub start: usize,
+    pub end: usize,
+    pub plist: V,
 }
 
 impl Value {
+    /// Return the cons cell `(car . cdr)`, like `cons` in Emacs does.
+    ///
+    /// When `cdr` is a list, the result is a list too.
+    pub fn cons(car: Value, cdr: Value) -> Value {
+        Self::dotted_list(vec![car], cdr)
+    }
+
+    /// Return the list of `items` which ends with `tail` instead of `nil`,
+    /// like `(1 2 . 3)`.
+    pub fn dotted_list(mut items: Vec<Value>, tail: Value) -> Value {
+        match tail {
+            Self::Nil => Self::list(items),
+            Self::List(rest) => {
+                items.extend(rest);
+                Self::List(items)
+            }
+            tail => items
+                .into_iter()
+                .rev()
+                .fold(tail, |cdr, car| Self::Cons(Box::new(car), Box::new(cdr))),
+        }
+    }
+
+    /// Return the proper list of `items`, the empty list is `nil`.
+    pub fn list(items: Vec<Value>) -> Value {
+        if items.is_empty() {
+            Self::Nil
+        } else {
+            Self::List(items)
+        }
+    }
+
+    #[allow(dead_code)]
     fn text(self) -> Option<String> {
         match self {
             Self::Symbol(s) | Self::String(s) => Some(s),
@@ -29,7 +109,7 @@ impl Value {
 
     pub fn as_string(self) -> Option<String> {
         match self {
-            Self::String(s) => Some(s),
+            Self::String(s) | Self::Propertized(s, _) => Some(s),
             _ => None,
         }
     }
@@ -51,6 +131,14 @@ impl Value {
     pub fn as_list(self) -> Option<Vec<Value>> {
         match self {
             Self::List(l) => Some(l),
+            Self::Nil => Some(Vec::new()),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector(self) -> Option<Vec<Value>> {
+        match self {
+            Self::Vector(v) => Some(v),
             _ => None,
         }
     }
@@ -63,21 +151,163 @@ impl Value {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub enum Error {
+/// Return true, if a list looks like an alist: every item of it is a cons or
+/// a list.
+pub(crate) fn is_alist(items: &[Value]) -> bool {
+    items
+        .iter()
+        .all(|item| matches!(item, Value::Cons(..) | Value::List(_)))
+}
+
+/// Return true, if a list looks like a plist: it has even length and every
+/// key is a symbol.
+pub(crate) fn is_plist(items: &[Value]) -> bool {
+    items.len().is_multiple_of(2)
+        && items
+            .iter()
+            .step_by(2)
+            .all(|k| matches!(k, Value::Symbol(_)))
+}
+
+/// Return the key-value pairs of a hash table, an alist or a plist.
+pub(crate) fn into_pairs(value: Value) -> Result<Vec<(Value, Value)>, Value> {
+    match value {
+        Value::HashTable { data, .. } => Ok(data),
+        Value::Nil => Ok(Vec::new()),
+        Value::List(items) if is_alist(&items) => Ok(items
+            .into_iter()
+            .map(|item| match item {
+                Value::Cons(car, cdr) => (*car, *cdr),
+                Value::List(items) => {
+                    // an empty list isn't built by the reader, but its car
+                    // is `nil` like the car of `nil`
+                    let mut items = items.into_iter();
+                    let key = items.next().unwrap_or(Value::Nil);
+                    (key, Value::list(items.collect()))
+                }
+                _ => unreachable!(),
+            })
+            .collect()),
+        Value::List(items) if is_plist(&items) => {
+            let mut pairs = Vec::with_capacity(items.len() / 2);
+            let mut items = items.into_iter();
+            while let (Some(key), Some(val)) = (items.next(), items.next()) {
+                pairs.push((key, val));
+            }
+            Ok(pairs)
+        }
+        value => Err(value),
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ErrorKind {
     UnbalancedExpr,
     EndOfInput,
     UnexpectedDot,
     InvalidNumber,
+    InvalidEscape,
+    InvalidSyntax,
+}
+
+impl Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::UnbalancedExpr => "unbalanced parentheses",
+            Self::EndOfInput => "unexpected end of input",
+            Self::UnexpectedDot => "unexpected dot",
+            Self::InvalidNumber => "invalid number",
+            Self::InvalidEscape => "invalid escape sequence",
+            Self::InvalidSyntax => "invalid syntax",
+        })
+    }
 }
 
+/// An error of the lisp reader and the place in the source where it happened.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Error {
+    pub kind: ErrorKind,
+    /// the byte offset where the reader stopped
+    pub offset: usize,
+    /// the line of `offset`, starting from 1
+    pub line: usize,
+    /// the column of `offset` in characters, starting from 1
+    pub column: usize,
+    /// the text of the line around `offset`
+    pub snippet: String,
+}
+
+// how many characters before and after an error are shown in a snippet
+const SNIPPET_RADIUS: usize = 20;
+
+impl Error {
+    /// Return the error of a given kind which happened at the byte `offset` of
+    /// `src`.
+    pub fn new(kind: ErrorKind, src: &str, offset: usize) -> Self {
+        let mut offset = offset.min(src.len());
+        while !src.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let (before, after) = src.split_at(offset);
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let before = &before[line_start..];
+        let after = after.split('\n').next().unwrap_or_default();
+
+        let column = before.chars().count() + 1;
+        let snippet = before
+            .chars()
+            .skip((column - 1).saturating_sub(SNIPPET_RADIUS))
+            .chain(after.chars().take(SNIPPET_RADIUS))
+            .collect();
+        Self {
+            kind,
+            offset,
+            line: src[..offset].matches('\n').count() + 1,
+            column,
+            snippet,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {} (byte {})",
+            self.kind, self.line, self.column, self.offset
+        )?;
+        if !self.snippet.is_empty() {
+            write!(f, " near `{}`", self.snippet)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
+
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 
 pub fn parse_string(s: &str) -> Result<Value> {
+    parse_borrowed(s).map(ValueRef::into_owned)
+}
+
+/// Like [`parse_string`], but return a value which borrows strings and
+/// symbols from `s`.
+pub fn parse_borrowed(s: &str) -> Result<ValueRef<'_>> {
     let mut parser = Parser::new(s);
     parser.parse()
 }
 
+/// Return the string of `s`, if it's one string literal like `"say \"hi\""`
+/// and nothing else.
+pub fn parse_string_literal(s: &str) -> Option<Cow<'_, str>> {
+    let mut parser = Parser::new(s);
+    match parser.next_form()? {
+        Ok((_, ValueRef::String(string))) if parser.next_form().is_none() => Some(string),
+        _ => None,
+    }
+}
+
 impl FromStr for Value {
     type Err = Error;
 
@@ -87,36 +317,16 @@ impl FromStr for Value {
 }
 
 #[derive(Debug)]
-pub enum Event {
-    Parsed(Value),
+pub enum Event<'a> {
+    Parsed(ValueRef<'a>),
     Skipped,
     ErrorHappened(Error),
     End,
 }
 
-impl From<Result<Value>> for Event {
-    fn from(value: Result<Value>) -> Self {
-        match value {
-            Ok(val) => Self::Parsed(val),
-            Err(e) => Self::ErrorHappened(e),
-        }
-    }
-}
-
-impl Into<Result<Value>> for Event {
-    fn into(self) -> Result<Value> {
-        match self {
-            Self::Parsed(val) => Ok(val),
-            Self::ErrorHappened(err) => Err(err),
-            Self::End => Err(Error::EndOfInput),
-            Self::Skipped => todo!(),
-        }
-    }
-}
-
-impl Event {
+impl<'a> Event<'a> {
     #[allow(dead_code)]
-    fn parsed(self) -> Option<Value> {
+    fn parsed(self) -> Option<ValueRef<'a>> {
         use Event::*;
 
         match self {
@@ -161,171 +371,448 @@ impl ListType {
 
 struct Parser<'a> {
     cursor: usize,
-    len: usize,
-    src: &'a [u8],
+    src: &'a str,
 }
 
 impl<'a> Parser<'a> {
     fn new(src: &'a str) -> Self {
-        Parser {
-            src: src.as_bytes(),
-            cursor: 0,
-            len: src.len(),
-        }
+        Parser { src, cursor: 0 }
     }
 
-    fn parse(&mut self) -> Result<Value> {
+    fn parse(&mut self) -> Result<ValueRef<'a>> {
         loop {
             let ev = self.next_event();
 
             match ev {
                 Event::Parsed(val) => return Ok(val),
                 Event::Skipped => continue,
-                Event::End => return Err(Error::UnbalancedExpr),
+                Event::End => return Err(self.error(ErrorKind::UnbalancedExpr)),
                 Event::ErrorHappened(err) => return Err(err),
             }
         }
     }
 
-    fn next_event(&mut self) -> Event {
-        if self.ch().is_none() {
-            return Event::End;
+    /// Read the next top-level form and the offset where it starts, return
+    /// `None` at the end of input.
+    fn next_form(&mut self) -> Option<Result<(usize, ValueRef<'a>)>> {
+        loop {
+            let start = self.cursor;
+            match self.next_event() {
+                Event::Parsed(val) => return Some(Ok((start, val))),
+                Event::Skipped => continue,
+                Event::End => return None,
+                Event::ErrorHappened(err) => return Some(Err(err)),
+            }
         }
+    }
+
+    /// Return the error of a given kind at the cursor.
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error::new(kind, self.src, self.cursor)
+    }
 
-        let ch = self.ch().unwrap();
+    fn next_event(&mut self) -> Event<'a> {
+        match self.read_event() {
+            Ok(ev) => ev,
+            // the cursor stays where the reader failed, so an error of an
+            // inner form is reported at the same place by outer ones
+            Err(kind) => Event::ErrorHappened(self.error(kind)),
+        }
+    }
+
+    fn read_event(&mut self) -> Result<Event<'a>, ErrorKind> {
+        let Some(ch) = self.ch() else {
+            return Ok(Event::End);
+        };
 
         use Event::*;
-        match ch {
+        let val = match ch {
             ch if ch.is_whitespace() => {
                 self.chop_spaces();
-                Event::Skipped
+                return Ok(Skipped);
             }
-            ch if ch.is_numeric() || ch == '-' => return self.parse_numeric_or_symbol().into(),
-            '"' => return self.parse_string().into(),
-            '[' | '(' => return self.parse_list_or_cons().into(),
-            '.' => return ErrorHappened(Error::UnexpectedDot),
-            ')' | ']' => return ErrorHappened(Error::UnbalancedExpr),
-            _ => return self.parse_symbol().into(),
-        }
+            ';' => {
+                self.chop_while(|c| c != '\n');
+                return Ok(Skipped);
+            }
+            '"' => self.parse_string()?,
+            '[' | '(' => self.parse_list_or_cons()?,
+            ')' | ']' => return Err(ErrorKind::UnbalancedExpr),
+            '.' if self.at_dot() => return Err(ErrorKind::UnexpectedDot),
+            '\'' => self.parse_quoted("quote", 1)?,
+            '`' => self.parse_quoted("`", 1)?,
+            ',' if self.nth_ch(1) == Some('@') => self.parse_quoted(",@", 2)?,
+            ',' => self.parse_quoted(",", 1)?,
+            '?' => self.parse_char()?,
+            '#' => self.parse_hash_syntax()?,
+            _ => self.parse_token()?,
+        };
+        Ok(Parsed(val))
     }
 
     fn ch(&self) -> Option<char> {
-        self.is_not_empty().then(|| self.src[self.cursor] as char)
+        self.nth_ch(0)
     }
 
-    fn chop_word(&mut self) -> String {
-        self.take_while(|c| c != ' ' && c != ')' && c != ']' && c != '\n')
+    fn nth_ch(&self, n: usize) -> Option<char> {
+        self.src[self.cursor..].chars().nth(n)
     }
 
-    const fn is_not_empty(&self) -> bool {
-        self.cursor < self.len
+    /// Return true, if the cursor is on a dot of a dotted pair, and not on a
+    /// symbol or a number which starts with a dot, like `.5`.
+    fn at_dot(&self) -> bool {
+        self.ch() == Some('.') && self.nth_ch(1).is_none_or(is_delimiter)
     }
 
     fn chop_spaces(&mut self) {
         self.chop_while(char::is_whitespace);
     }
 
-    fn parse_string(&mut self) -> Result<Value> {
+    fn parse_string(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
         assert_eq!(self.ch(), Some('"'));
         self.chop(1);
-        let mut str = String::new();
+        // a string without escapes is borrowed from the source as it is
+        let rest = self.substr(self.cursor, self.src.len());
+        if let Some(end) = rest
+            .find(['"', '\\'])
+            .filter(|&i| rest[i..].starts_with('"'))
+        {
+            self.chop(end + 1);
+            return Ok(ValueRef::String(Cow::Borrowed(&rest[..end])));
+        }
+        let mut bytes = Vec::new();
+        // Emacs reads an octal or hex escape of a byte above 127, like `\377`,
+        // as a raw byte, and then the string is unibyte
+        let mut unibyte = false;
+        let mut buf = [0; 4];
         loop {
-            match self.ch().ok_or(Error::UnbalancedExpr)? {
-                '"' => {
-                    self.chop(1);
-                    break;
-                }
+            let ch = match self.chop_ch().ok_or(ErrorKind::EndOfInput)? {
+                '"' => break,
                 '\\' => {
-                    self.chop(1);
-                    str.push(escape_char(self.chop_ch().ok_or(Error::UnbalancedExpr)?));
+                    let byte_escape = matches!(self.ch(), Some('0'..='7' | 'x'));
+                    match self.parse_escape(true)? {
+                        Some(code) if byte_escape && (128..256).contains(&code) => {
+                            bytes.push(code as u8);
+                            unibyte = true;
+                            continue;
+                        }
+                        Some(code) => {
+                            char::from_u32(code as u32).ok_or(ErrorKind::InvalidEscape)?
+                        }
+                        None => continue,
+                    }
+                }
+                ch => ch,
+            };
+            bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+        }
+        match String::from_utf8(bytes) {
+            Ok(s) if !unibyte => Ok(ValueRef::String(Cow::Owned(s))),
+            Ok(s) => Ok(ValueRef::Unibyte(s.into_bytes())),
+            Err(err) => Ok(ValueRef::Unibyte(err.into_bytes())),
+        }
+    }
+
+    /// Parse the rest of an escape sequence after a backslash, and return
+    /// the code of the character it stands for.
+    ///
+    /// Inside strings `\ ` and a backslash before a newline stand for
+    /// nothing, for them `None` is returned.
+    fn parse_escape(&mut self, in_string: bool) -> Result<Option<i64>, ErrorKind> {
+        let ch = self.chop_ch().ok_or(ErrorKind::EndOfInput)?;
+        let code = match ch {
+            '\n' | ' ' if in_string => return Ok(None),
+            'a' => 7,
+            'b' => 8,
+            'd' => 127,
+            'e' => 27,
+            'f' => 12,
+            'n' => 10,
+            'r' => 13,
+            't' => 9,
+            'v' => 11,
+            'x' => self.parse_hex_digits(usize::MAX)?,
+            'u' => self.parse_hex_digits(4)?,
+            'U' => self.parse_hex_digits(8)?,
+            'N' => self.parse_named_char()?,
+            '0'..='7' => {
+                let mut code = ch.to_digit(8).unwrap() as i64;
+                for _ in 0..2 {
+                    match self.ch().and_then(|c| c.to_digit(8)) {
+                        Some(d) => {
+                            code = code * 8 + d as i64;
+                            self.chop(1);
+                        }
+                        None => break,
+                    }
                 }
-                ch => {
-                    str.push(ch);
+                code
+            }
+            '^' => control_char(self.parse_modified_char(in_string)?),
+            'C' if self.ch() == Some('-') => {
+                self.chop(1);
+                control_char(self.parse_modified_char(in_string)?)
+            }
+            'M' | 'S' | 'H' | 's' | 'A' if self.ch() == Some('-') => {
+                self.chop(1);
+                let modifier = match ch {
+                    'M' => META_MODIFIER,
+                    'S' => SHIFT_MODIFIER,
+                    'H' => HYPER_MODIFIER,
+                    's' => SUPER_MODIFIER,
+                    _ => ALT_MODIFIER,
+                };
+                self.parse_modified_char(in_string)? | modifier
+            }
+            's' => ' ' as i64,
+            ch => ch as i64,
+        };
+        Ok(Some(code))
+    }
+
+    /// Parse a character after a modifier like `\C-` or `\M-`.
+    fn parse_modified_char(&mut self, in_string: bool) -> Result<i64, ErrorKind> {
+        match self.chop_ch().ok_or(ErrorKind::EndOfInput)? {
+            '\\' => self
+                .parse_escape(in_string)?
+                .ok_or(ErrorKind::InvalidEscape),
+            ch => Ok(ch as i64),
+        }
+    }
+
+    fn parse_hex_digits(&mut self, max: usize) -> Result<i64, ErrorKind> {
+        let beg = self.cursor;
+        let mut code: i64 = 0;
+        while self.cursor - beg < max {
+            match self.ch().and_then(|c| c.to_digit(16)) {
+                Some(d) => {
+                    code = code
+                        .checked_mul(16)
+                        .and_then(|c| c.checked_add(d as i64))
+                        .ok_or(ErrorKind::InvalidEscape)?;
                     self.chop(1);
                 }
+                None => break,
             }
         }
-        Ok(Value::String(str))
+        let len = self.cursor - beg;
+        if len == 0 || (max != usize::MAX && len != max) {
+            return Err(ErrorKind::InvalidEscape);
+        }
+        Ok(code)
+    }
+
+    /// Parse `{U+XXXX}` after `\N`, names of characters are not supported.
+    fn parse_named_char(&mut self) -> Result<i64, ErrorKind> {
+        if self.chop_ch() != Some('{') {
+            return Err(ErrorKind::InvalidEscape);
+        }
+        let name = self.take_while(|c| c != '}');
+        if self.chop_ch() != Some('}') {
+            return Err(ErrorKind::InvalidEscape);
+        }
+        name.strip_prefix("U+")
+            .and_then(|hex| i64::from_str_radix(hex, 16).ok())
+            .ok_or(ErrorKind::InvalidEscape)
     }
 
-    fn parse_list_or_cons(&mut self) -> Result<Value> {
-        let op = self.ch().ok_or(Error::UnbalancedExpr)?;
+    /// Parse a character literal like `?a` or `?\C-x`, Emacs represents
+    /// characters as integers.
+    fn parse_char(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
+        assert_eq!(self.ch(), Some('?'));
+        self.chop(1);
+        let code = match self.chop_ch().ok_or(ErrorKind::EndOfInput)? {
+            '\\' => self.parse_escape(false)?.ok_or(ErrorKind::InvalidEscape)?,
+            ch => ch as i64,
+        };
+        if self.ch().is_some_and(|c| !is_delimiter(c)) {
+            return Err(ErrorKind::InvalidSyntax);
+        }
+        Ok(ValueRef::Integer(code))
+    }
+
+    /// Parse a form after one of the quote characters, so `'a` becomes
+    /// `(quote a)` and `,@a` becomes `(\,@ a)`.
+    fn parse_quoted(
+        &mut self,
+        name: &'static str,
+        prefix_len: usize,
+    ) -> Result<ValueRef<'a>, ErrorKind> {
+        self.chop(prefix_len);
+        let val = self.next_parsed()?;
+        Ok(ValueRef::List(vec![ValueRef::Symbol(name.into()), val]))
+    }
+
+    /// Parse a syntax which starts with `#`.
+    fn parse_hash_syntax(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
+        assert_eq!(self.ch(), Some('#'));
+        self.chop(1);
+        match self.ch().ok_or(ErrorKind::EndOfInput)? {
+            's' if self.nth_ch(1) == Some('(') => {
+                self.chop(1);
+                match self.parse_list_or_cons()? {
+                    ValueRef::List(items) => record_or_hash_table(items),
+                    _ => Err(ErrorKind::InvalidSyntax),
+                }
+            }
+            '(' => match self.parse_list_or_cons()? {
+                ValueRef::List(items) => propertized_string(items),
+                _ => Err(ErrorKind::InvalidSyntax),
+            },
+            '\'' => self.parse_quoted("function", 1),
+            '#' => {
+                self.chop(1);
+                Ok(ValueRef::Symbol("".into()))
+            }
+            ':' => {
+                self.chop(1);
+                let (name, _) = self.read_token()?;
+                Ok(ValueRef::Symbol(name))
+            }
+            'x' | 'X' => self.parse_radix_integer(16, 1),
+            'o' | 'O' => self.parse_radix_integer(8, 1),
+            'b' | 'B' => self.parse_radix_integer(2, 1),
+            ch if ch.is_ascii_digit() => {
+                let digits = self.take_while(|c| c.is_ascii_digit());
+                if self.ch() != Some('r') {
+                    return Err(ErrorKind::InvalidSyntax);
+                }
+                let radix = digits.parse().map_err(|_| ErrorKind::InvalidSyntax)?;
+                if !(2..=36).contains(&radix) {
+                    return Err(ErrorKind::InvalidSyntax);
+                }
+                self.parse_radix_integer(radix, 1)
+            }
+            _ => Err(ErrorKind::InvalidSyntax),
+        }
+    }
+
+    /// Parse an integer like `#x1F` after `#`, `prefix_len` is the length of
+    /// the radix prefix (like `x`).
+    fn parse_radix_integer(
+        &mut self,
+        radix: u32,
+        prefix_len: usize,
+    ) -> Result<ValueRef<'a>, ErrorKind> {
+        self.chop(prefix_len);
+        let (digits, _) = self.read_token()?;
+        match i64::from_str_radix(&digits, radix) {
+            Ok(n) => Ok(ValueRef::Integer(n)),
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    IntErrorKind::PosOverflow | IntErrorKind::NegOverflow
+                ) =>
+            {
+                Ok(ValueRef::BigInt(
+                    to_decimal(&digits, radix).ok_or(ErrorKind::InvalidNumber)?,
+                ))
+            }
+            Err(_) => Err(ErrorKind::InvalidNumber),
+        }
+    }
+
+    fn parse_list_or_cons(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
+        let op = self.ch().ok_or(ErrorKind::UnbalancedExpr)?;
         self.chop(1);
         let kind = ListType::from_open(op).unwrap();
         let cl = kind.close();
 
-        let mut lst: Vec<Value> = Vec::new();
+        let mut lst = Vec::new();
 
         loop {
             let ch = self.ch();
             if ch == Some(cl) {
                 self.chop(1);
                 break;
-            } else if ch == Some('.') {
+            } else if self.at_dot() {
+                if matches!(kind, ListType::Vec) || lst.is_empty() {
+                    return Err(ErrorKind::UnexpectedDot);
+                }
                 self.chop(1);
-                let car = if lst.len() == 1 {
-                    lst[0].clone()
-                } else {
-                    Value::List(lst)
-                };
-                let cdr = self.next_parsed().or(Err(Error::UnexpectedDot))?;
-                return Ok(Value::Cons(Box::new(car), Box::new(cdr)));
+                let tail = self.next_parsed().or(Err(ErrorKind::UnexpectedDot))?;
+                self.chop_spaces_and_comments();
+                if self.ch() != Some(cl) {
+                    return Err(ErrorKind::UnexpectedDot);
+                }
+                self.chop(1);
+                return Ok(ValueRef::dotted_list(lst, tail));
             }
             let ev = self.next_event();
             match ev {
                 Event::Parsed(val) => lst.push(val),
                 Event::Skipped => continue,
-                Event::End => return Err(Error::UnbalancedExpr),
-                Event::ErrorHappened(err) => return Err(err),
+                Event::End => return Err(ErrorKind::UnbalancedExpr),
+                Event::ErrorHappened(err) => return Err(err.kind),
             }
         }
 
-        return Ok(Value::List(lst));
+        match kind {
+            ListType::Vec => Ok(ValueRef::Vector(lst)),
+            ListType::List => Ok(ValueRef::list(lst)),
+        }
     }
 
-    fn parse_symbol(&mut self) -> Result<Value> {
-        assert!(self.ch().is_some() && is_identifier_char(self.ch().unwrap()));
-        let name = self.take_while(is_identifier_char);
-        Ok(Value::Symbol(name))
+    fn chop_spaces_and_comments(&mut self) {
+        loop {
+            match self.ch() {
+                Some(ch) if ch.is_whitespace() => self.chop_spaces(),
+                Some(';') => self.chop_while(|c| c != '\n'),
+                _ => break,
+            }
+        }
     }
 
-    fn next_parsed(&mut self) -> Result<Value> {
-        loop {
-            let ev = self.next_event();
-            match ev {
-                Event::Skipped => continue,
-                Event::Parsed(val) => return Ok(val),
-                Event::End => return Err(Error::EndOfInput),
-                Event::ErrorHappened(err) => return Err(err),
+    /// Parse a number or a symbol, Emacs doesn't distinguish them until the
+    /// whole token is read: `1` is a number, while `1+` is a symbol.
+    fn parse_token(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
+        let (name, escaped) = self.read_token()?;
+        if !escaped {
+            if let Some(num) = parse_number(&name)? {
+                return Ok(num);
+            }
+            if name == "nil" {
+                return Ok(ValueRef::Nil);
             }
         }
+        Ok(ValueRef::Symbol(name))
     }
 
-    fn parse_numeric_or_symbol(&mut self) -> Result<Value> {
+    /// Read a token until a delimiter, return its text with processed
+    /// backslash escapes and whether there were any escapes.
+    fn read_token(&mut self) -> Result<(Cow<'a, str>, bool), ErrorKind> {
         let beg = self.cursor;
-        self.parse_numeric().or_else(|_| {
-            self.cursor = beg;
-            let symbol = self.parse_symbol()?;
-            let name = symbol.text().unwrap();
-            if name.contains('.') {
-                Err(Error::InvalidNumber)
+        self.chop_while(|c| !is_delimiter(c) && c != '\\');
+        if self.ch() != Some('\\') {
+            return Ok((Cow::Borrowed(self.substr(beg, self.cursor)), false));
+        }
+        let mut name = self.substr(beg, self.cursor).to_string();
+        let mut escaped = false;
+        while let Some(ch) = self.ch().filter(|c| !is_delimiter(*c)) {
+            self.chop(ch.len_utf8());
+            if ch == '\\' {
+                name.push(self.chop_ch().ok_or(ErrorKind::EndOfInput)?);
+                escaped = true;
             } else {
-                Ok(Value::Symbol(name))
+                name.push(ch);
             }
-        })
+        }
+        Ok((Cow::Owned(name), escaped))
     }
 
-    fn parse_numeric(&mut self) -> Result<Value> {
-        let w = self.chop_word();
-        if w.contains('.') {
-            return w.parse().map(Value::Real).map_err(|_| Error::InvalidNumber);
+    fn next_parsed(&mut self) -> Result<ValueRef<'a>, ErrorKind> {
+        loop {
+            let ev = self.next_event();
+            match ev {
+                Event::Skipped => continue,
+                Event::Parsed(val) => return Ok(val),
+                Event::End => return Err(ErrorKind::EndOfInput),
+                Event::ErrorHappened(err) => return Err(err.kind),
+            }
         }
-        w.parse()
-            .map(Value::Integer)
-            .map_err(|_| Error::InvalidNumber)
     }
 
-    fn take_while(&mut self, f: fn(char) -> bool) -> String {
+    fn take_while(&mut self, f: fn(char) -> bool) -> &'a str {
         let beg = self.cursor;
         self.chop_while(f);
         let end = self.cursor;
@@ -333,28 +820,30 @@ impl<'a> Parser<'a> {
     }
 
     fn chop_while(&mut self, f: fn(char) -> bool) {
-        while self.ch().map(f).unwrap_or(false) {
-            self.chop(1);
+        while let Some(ch) = self.ch().filter(|c| f(*c)) {
+            self.chop(ch.len_utf8());
         }
     }
 
+    /// Move the cursor by `n` bytes.
     fn chop(&mut self, n: usize) {
         self.cursor += n;
     }
 
     fn chop_ch(&mut self) -> Option<char> {
         let res = self.ch();
-        self.chop(1);
+        // at the end of input the cursor stays at the end
+        self.chop(res.map_or(0, char::len_utf8));
         res
     }
 
-    fn substr(&mut self, beg: usize, end: usize) -> String {
-        String::from_utf8(self.src[beg..end].to_vec()).unwrap()
+    fn substr(&self, beg: usize, end: usize) -> &'a str {
+        &self.src[beg..end]
     }
 
     #[allow(dead_code)]
     fn progress(&self) {
-        println!("{}", String::from_utf8(self.src.to_vec()).unwrap());
+        println!("{}", self.src);
         for _ in 0..self.cursor {
             print!(" ");
         }
@@ -362,27 +851,208 @@ impl<'a> Parser<'a> {
     }
 }
 
-fn is_identifier_char(ch: char) -> bool {
-    !(ch.is_whitespace()
-        || ch == '['
-        || ch == ']'
-        || ch == '('
-        || ch == ')'
-        || ch == '.'
-        || ch == '"'
-        || ch == '\'')
+// bits which modifiers like `\M-` set in a character code, see
+// `char-resolve-modifier-mask` in Emacs
+const ALT_MODIFIER: i64 = 1 << 22;
+const SUPER_MODIFIER: i64 = 1 << 23;
+const HYPER_MODIFIER: i64 = 1 << 24;
+const SHIFT_MODIFIER: i64 = 1 << 25;
+const CTRL_MODIFIER: i64 = 1 << 26;
+const META_MODIFIER: i64 = 1 << 27;
+
+/// Apply the control modifier to a character like the Emacs reader does:
+/// `\C-a` is 1, `\C-?` is DEL, other characters get the control bit.
+fn control_char(code: i64) -> i64 {
+    if code & 0o177 == '?' as i64 {
+        0o177 | (code & !0o177)
+    } else if (0o101..=0o132).contains(&(code & 0o137)) || (0o100..=0o137).contains(&(code & 0o177))
+    {
+        code & (0o37 | !0o177)
+    } else {
+        code | CTRL_MODIFIER
+    }
+}
+
+/// Return true, if a character ends a symbol or a number.
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace()
+        || matches!(
+            ch,
+            '"' | '\'' | ';' | '#' | '(' | ')' | '[' | ']' | '`' | ','
+        )
 }
 
-fn escape_char(ch: char) -> char {
-    match ch {
-        '"' => '"',
-        'n' => '\n',
-        't' => '\t',
-        ch if ch.is_numeric() => ('\0' as u8 + ch.to_digit(10).unwrap() as u8) as char,
-        _ => ch,
+/// Parse the text of a token as a number, if it has the syntax of a number.
+///
+/// `Ok(None)` means that the token is a symbol, `Err` means that the token
+/// looks like a number, but it can't be represented.
+fn parse_number(s: &str) -> Result<Option<ValueRef<'static>>, ErrorKind> {
+    let (sign, body) = match s.strip_prefix(['-', '+']) {
+        Some(body) => (&s[..1], body),
+        None => ("", s),
+    };
+    let (mantissa, exponent) = match body.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (body, None),
+    };
+    let (int, frac) = match mantissa.split_once('.') {
+        Some((int, frac)) => (int, Some(frac)),
+        None => (mantissa, None),
+    };
+
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    if !all_digits(int) || !frac.is_none_or(all_digits) {
+        return Ok(None);
+    }
+    let lead_int = !int.is_empty();
+    let trail_int = frac.is_some_and(|f| !f.is_empty());
+
+    match exponent {
+        // "1" and "1." are integers, the ones which don't fit into `i64` are
+        // bignums
+        None if lead_int && !trail_int => {
+            let text = format!("{sign}{int}");
+            Ok(Some(match text.parse() {
+                Ok(n) => ValueRef::Integer(n),
+                Err(_) => ValueRef::BigInt(to_decimal(&text, 10).ok_or(ErrorKind::InvalidNumber)?),
+            }))
+        }
+        // ".5" and "1.5" are floats
+        None if trail_int => parse_float(sign, int, frac, None),
+        // "1e5", "1.e5" and ".5e5" are floats too
+        // "1.0e+INF" and "0.0e+NaN" are the infinity and NaN
+        Some("+INF") if lead_int || trail_int => {
+            let inf = if sign == "-" {
+                f64::NEG_INFINITY
+            } else {
+                f64::INFINITY
+            };
+            Ok(Some(ValueRef::Real(inf)))
+        }
+        Some("+NaN") if lead_int || trail_int => {
+            let nan = if sign == "-" { -f64::NAN } else { f64::NAN };
+            Ok(Some(ValueRef::Real(nan)))
+        }
+        Some(exp) if lead_int || trail_int => {
+            let digits = exp.strip_prefix(['-', '+']).unwrap_or(exp);
+            if digits.is_empty() || !all_digits(digits) {
+                return Ok(None);
+            }
+            parse_float(sign, int, frac, Some(exp))
+        }
+        _ => Ok(None),
     }
 }
 
+fn parse_float(
+    sign: &str,
+    int: &str,
+    frac: Option<&str>,
+    exp: Option<&str>,
+) -> Result<Option<ValueRef<'static>>, ErrorKind> {
+    let int = if int.is_empty() { "0" } else { int };
+    let frac = frac.filter(|f| !f.is_empty()).unwrap_or("0");
+    let exp = exp.map(|e| format!("e{e}")).unwrap_or_default();
+    format!("{sign}{int}.{frac}{exp}")
+        .parse()
+        .map(|n| Some(ValueRef::Real(n)))
+        .map_err(|_| ErrorKind::InvalidNumber)
+}
+
+/// Convert the text of an integer which doesn't fit into `i64` to decimal
+/// digits, return `None` when it has a digit which is invalid in `radix`.
+fn to_decimal(text: &str, radix: u32) -> Option<String> {
+    const LIMB: u64 = 1_000_000_000;
+
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text.strip_prefix('+').unwrap_or(text)),
+    };
+    if digits.is_empty() {
+        return None;
+    }
+    // the number in base 10^9, the lowest limb first
+    let mut limbs = vec![0];
+    for ch in digits.chars() {
+        let mut carry = u64::from(ch.to_digit(radix)?);
+        for limb in &mut limbs {
+            let n = *limb * u64::from(radix) + carry;
+            *limb = n % LIMB;
+            carry = n / LIMB;
+        }
+        if carry > 0 {
+            limbs.push(carry);
+        }
+    }
+
+    let mut limbs = limbs.into_iter().rev();
+    let mut text = format!("{sign}{}", limbs.next().unwrap());
+    for limb in limbs {
+        write!(text, "{limb:09}").unwrap();
+    }
+    Some(text)
+}
+
+/// Build a value from the items of `#s(...)`: a hash table when the first
+/// item is `hash-table`, otherwise a record.
+fn record_or_hash_table(items: Vec<ValueRef>) -> Result<ValueRef, ErrorKind> {
+    match items.first() {
+        Some(ValueRef::Symbol(name)) if name == "hash-table" => {}
+        Some(_) => return Ok(ValueRef::Record(items)),
+        None => return Err(ErrorKind::InvalidSyntax),
+    }
+
+    let mut test = None;
+    let mut data = Vec::new();
+    let mut props = items.into_iter().skip(1);
+    while let Some(prop) = props.next() {
+        let val = props.next().ok_or(ErrorKind::InvalidSyntax)?;
+        match prop {
+            ValueRef::Symbol(name) if name == "test" => {
+                test = Some(val.as_symbol().ok_or(ErrorKind::InvalidSyntax)?);
+            }
+            ValueRef::Symbol(name) if name == "data" => {
+                let mut kvs = match val {
+                    ValueRef::List(kvs) => kvs.into_iter(),
+                    ValueRef::Nil => Vec::new().into_iter(),
+                    _ => return Err(ErrorKind::InvalidSyntax),
+                };
+                while let Some(key) = kvs.next() {
+                    data.push((key, kvs.next().ok_or(ErrorKind::InvalidSyntax)?));
+                }
+            }
+            // `size`, `rehash-size` and others aren't interesting
+            _ => {}
+        }
+    }
+    Ok(ValueRef::HashTable { test, data })
+}
+
+/// Build a string with text properties from the items of `#(...)`.
+fn propertized_string(items: Vec<ValueRef>) -> Result<ValueRef, ErrorKind> {
+    let mut items = items.into_iter();
+    let text = match items.next() {
+        Some(ValueRef::String(text)) => text,
+        _ => return Err(ErrorKind::InvalidSyntax),
+    };
+
+    let mut props = Vec::new();
+    while let Some(start) = items.next() {
+        let (Some(end), Some(plist)) = (items.next(), items.next()) else {
+            return Err(ErrorKind::InvalidSyntax);
+        };
+        let (ValueRef::Integer(start), ValueRef::Integer(end)) = (start, end) else {
+            return Err(ErrorKind::InvalidSyntax);
+        };
+        props.push(TextProperty {
+            start: usize::try_from(start).map_err(|_| ErrorKind::InvalidSyntax)?,
+            end: usize::try_from(end).map_err(|_| ErrorKind::InvalidSyntax)?,
+            plist,
+        });
+    }
+    Ok(ValueRef::Propertized(text, props))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -443,7 +1113,7 @@ mod tests {
                 Some(&Value::List(vec![
                     Value::Integer(5),
                     Value::Integer(6),
-                    Value::List(vec![Value::Integer(7), Value::Integer(8)])
+                    Value::Vector(vec![Value::Integer(7), Value::Integer(8)])
                 ]))
             );
             assert_eq!(nums.next(), None);
@@ -456,17 +1126,57 @@ mod tests {
     fn test_parse_cons() {
         let src = "(1 2 3 4 . 7)";
         let res: Value = src.parse().unwrap();
+        let cons = |car: i64, cdr: Value| Value::Cons(Box::new(Value::Integer(car)), Box::new(cdr));
+        assert_eq!(res, cons(1, cons(2, cons(3, cons(4, Value::Integer(7))))));
+    }
+
+    #[test]
+    fn test_parse_dotted_proper_lists() {
+        let one_two_three = Value::List(vec![
+            Value::Integer(1),
+            Value::Integer(2),
+            Value::Integer(3),
+        ]);
+        assert_eq!("(1 . (2 3))".parse(), Ok(one_two_three.clone()));
+        assert_eq!("(1 2 . (3 . nil))".parse(), Ok(one_two_three));
         assert_eq!(
-            res,
+            "(1 . nil)".parse(),
+            Ok(Value::List(vec![Value::Integer(1)]))
+        );
+    }
+
+    #[test]
+    fn test_parse_nil() {
+        assert_eq!("nil".parse(), Ok(Value::Nil));
+        assert_eq!("()".parse(), Ok(Value::Nil));
+        assert_eq!("[]".parse(), Ok(Value::Vector(vec![])));
+        assert_eq!("[nil]".parse(), Ok(Value::Vector(vec![Value::Nil])));
+    }
+
+    #[test]
+    fn test_parse_alist() {
+        let src = r#"(("ID" . "x") ("CATEGORY" . "y"))"#;
+        let pair = |k: &str, v: &str| {
             Value::Cons(
-                Box::new(Value::List(vec![
-                    Value::Integer(1),
-                    Value::Integer(2),
-                    Value::Integer(3),
-                    Value::Integer(4)
-                ])),
-                Box::new(Value::Integer(7))
+                Box::new(Value::String(k.into())),
+                Box::new(Value::String(v.into())),
             )
+        };
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![pair("ID", "x"), pair("CATEGORY", "y")]))
+        );
+    }
+
+    #[test]
+    fn test_dot_in_vector() {
+        assert_eq!(
+            "[1 . 2]".parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::UnexpectedDot)
+        );
+        assert_eq!(
+            "(. 2)".parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::UnexpectedDot)
         );
     }
 
@@ -506,20 +1216,268 @@ mod tests {
     fn test_unbalanced_expr() {
         let src = " ( jdeidje ] )";
         let actual = src.parse::<Value>().unwrap_err();
-        assert_eq!(actual, Error::UnbalancedExpr)
+        assert_eq!(actual.kind, ErrorKind::UnbalancedExpr)
     }
 
     #[test]
     fn test_unbalanced_list_with_paren_at_end() {
         let src = " ( jdeidje ";
         let actual = src.parse::<Value>().unwrap_err();
-        assert_eq!(actual, Error::UnbalancedExpr)
+        assert_eq!(actual.kind, ErrorKind::UnbalancedExpr)
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        for src in ["\"abc", "\"abc\\\"", "(\"abc\\n"] {
+            let actual = src.parse::<Value>().unwrap_err();
+            assert_eq!(actual.kind, ErrorKind::EndOfInput);
+            assert_eq!(actual.offset, src.len());
+            // the reader stops at the end instead of stepping past it
+            let mut parser = Parser::new(src);
+            assert!(parser.parse().is_err());
+            assert_eq!(parser.cursor, src.len());
+        }
     }
 
     #[test]
     fn test_unexcepted_dot() {
         let src = " ( [ 1 2 3 4 . ] )";
         let actual = src.parse::<Value>().unwrap_err();
-        assert_eq!(actual, Error::UnexpectedDot)
+        assert_eq!(actual.kind, ErrorKind::UnexpectedDot)
+    }
+
+    #[test]
+    fn test_parse_quotes() {
+        let quoted = |name: &str, val: Value| Value::List(vec![Value::Symbol(name.into()), val]);
+        let a = || Value::Symbol("a".into());
+        assert_eq!("'a".parse(), Ok(quoted("quote", a())));
+        assert_eq!("#'a".parse(), Ok(quoted("function", a())));
+        assert_eq!(
+            "`(a ,a ,@a)".parse(),
+            Ok(quoted(
+                "`",
+                Value::List(vec![a(), quoted(",", a()), quoted(",@", a())])
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parse_char_literals() {
+        let chars = "(?a ?\\n ?\\( ?\\s ?\\C-a ?\\^? ?\\M-a ?\\x41 ?\\101 ?é)";
+        let expected = vec![97, 10, 40, 32, 1, 127, (1 << 27) | 97, 65, 65, 233];
+        assert_eq!(
+            chars.parse(),
+            Ok(Value::List(
+                expected.into_iter().map(Value::Integer).collect()
+            ))
+        );
+        assert_eq!(
+            "?ab".parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn test_parse_comments() {
+        let src = "; the olp of a node\n(\"a\" ; first\n \"b\")";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![
+                Value::String("a".into()),
+                Value::String("b".into())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let src = r#""\x41\ B \u00e9 \U0001F600 \N{U+E9} \101\t\e\
+end""#;
+        assert_eq!(
+            src.parse(),
+            Ok(Value::String("AB é 😀 é A\t\u{1b}end".into()))
+        );
+        assert_eq!(
+            r#""\u00""#.parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::InvalidEscape)
+        );
+    }
+
+    #[test]
+    fn test_parse_raw_bytes() {
+        assert_eq!(r#""\377a""#.parse(), Ok(Value::Unibyte(vec![0xff, b'a'])));
+        // an escape below 128 is an ASCII character
+        assert_eq!(r#""\x41""#.parse(), Ok(Value::String("A".into())));
+    }
+
+    #[test]
+    fn test_parse_non_ascii() {
+        let src = "(\"Второй закон Ньютона\" физика)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![
+                Value::String("Второй закон Ньютона".into()),
+                Value::Symbol("физика".into())
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_floats() {
+        let src = "(1e10 1.5e-3 .5 -1. +2 1.e2 1.2.3 1e e5)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![
+                Value::Real(1e10),
+                Value::Real(1.5e-3),
+                Value::Real(0.5),
+                Value::Integer(-1),
+                Value::Integer(2),
+                Value::Real(100.0),
+                Value::Symbol("1.2.3".into()),
+                Value::Symbol("1e".into()),
+                Value::Symbol("e5".into()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_special_floats() {
+        let parse = |src: &str| src.parse::<Value>().unwrap();
+        assert_eq!(parse("1.0e+INF"), Value::Real(f64::INFINITY));
+        assert_eq!(parse("-1.0e+INF"), Value::Real(f64::NEG_INFINITY));
+        assert!(matches!(parse("0.0e+NaN"), Value::Real(n) if n.is_nan()));
+        assert!(matches!(parse("-0.0"), Value::Real(n) if n == 0.0 && n.is_sign_negative()));
+        assert_eq!(parse("1e+INFx"), Value::Symbol("1e+INFx".into()));
+    }
+
+    #[test]
+    fn test_parse_bignums() {
+        let parse = |src: &str| src.parse::<Value>().unwrap();
+        assert_eq!(
+            parse("-000123456789012345678901234"),
+            Value::BigInt("-123456789012345678901234".into())
+        );
+        assert_eq!(parse("9223372036854775807"), Value::Integer(i64::MAX));
+        assert_eq!(
+            parse("9223372036854775808."),
+            Value::BigInt("9223372036854775808".into())
+        );
+        assert_eq!(
+            parse("#x10000000000000000"),
+            Value::BigInt("18446744073709551616".into())
+        );
+    }
+
+    #[test]
+    fn test_parse_symbol_escapes() {
+        let src = r"(a\ b \1 foo.bar ## #:x)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![
+                Value::Symbol("a b".into()),
+                Value::Symbol("1".into()),
+                Value::Symbol("foo.bar".into()),
+                Value::Symbol("".into()),
+                Value::Symbol("x".into()),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_radix_integers() {
+        let src = "(#x1F #o17 #b101 #24r1k)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::List(vec![
+                Value::Integer(31),
+                Value::Integer(15),
+                Value::Integer(5),
+                Value::Integer(44),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_hash_table() {
+        let src = "#s(hash-table size 2 test equal rehash-size 1.5 data (\"ID\" \"x\" a 1))";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::HashTable {
+                test: Some("equal".into()),
+                data: vec![
+                    (Value::String("ID".into()), Value::String("x".into())),
+                    (Value::Symbol("a".into()), Value::Integer(1)),
+                ],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_record() {
+        let src = "#s(org-roam-node \"id\" 1)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::Record(vec![
+                Value::Symbol("org-roam-node".into()),
+                Value::String("id".into()),
+                Value::Integer(1),
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_parse_propertized_string() {
+        let src = "#(\"text\" 0 3 (face bold) 3 4 nil)";
+        assert_eq!(
+            src.parse(),
+            Ok(Value::Propertized(
+                "text".into(),
+                vec![
+                    TextProperty {
+                        start: 0,
+                        end: 3,
+                        plist: Value::List(vec![
+                            Value::Symbol("face".into()),
+                            Value::Symbol("bold".into())
+                        ]),
+                    },
+                    TextProperty {
+                        start: 3,
+                        end: 4,
+                        plist: Value::Nil,
+                    },
+                ]
+            ))
+        );
+        assert_eq!(
+            "#(\"text\" 0 3)".parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::InvalidSyntax)
+        );
+    }
+
+    #[test]
+    fn test_dot_must_end_list() {
+        let src = "(1 . 2 3)";
+        assert_eq!(
+            src.parse::<Value>().map_err(|e| e.kind),
+            Err(ErrorKind::UnexpectedDot)
+        );
+    }
+
+    #[test]
+    fn test_error_position() {
+        let err = "(a\n (b 12x3 . ])".parse::<Value>().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::UnexpectedDot);
+        assert_eq!((err.offset, err.line, err.column), (14, 2, 12));
+        assert_eq!(err.snippet, " (b 12x3 . ])");
+        assert_eq!(
+            err.to_string(),
+            "unexpected dot at line 2, column 12 (byte 14) near ` (b 12x3 . ])`"
+        );
+
+        let err = "\"это ошибка \\xZZ\"".parse::<Value>().unwrap_err();
+        assert_eq!(err.kind, ErrorKind::InvalidEscape);
+        assert_eq!(err.column, 15);
     }
 }
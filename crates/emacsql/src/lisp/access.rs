@@ -0,0 +1,373 @@
+use std::slice;
+
+use super::{is_alist, is_plist, Error, ErrorKind, Parser, Result, Value, ValueRef};
+
+static NIL: Value = Value::Nil;
+
+/// A part of a lisp value borrowed from it.
+///
+/// `List` keeps all items in one vector, so the cdr of a proper list isn't a
+/// `Value` which can be borrowed, it's the slice `Items`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Cdr<'a> {
+    Value(&'a Value),
+    /// the rest of a proper list, the empty slice is `nil`
+    Items(&'a [Value]),
+}
+
+impl<'a> Cdr<'a> {
+    /// Return the borrowed value, or `None` for the rest of a list which
+    /// isn't empty.
+    pub fn as_value(self) -> Option<&'a Value> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Items([]) => Some(&NIL),
+            Self::Items(_) => None,
+        }
+    }
+
+    /// Return the value, the rest of a list is cloned into a new list.
+    pub fn to_value(self) -> Value {
+        match self {
+            Self::Value(value) => value.clone(),
+            Self::Items(items) => Value::list(items.to_vec()),
+        }
+    }
+
+    pub fn car(self) -> Option<&'a Value> {
+        match self {
+            Self::Value(value) => value.car(),
+            Self::Items(items) => items.first(),
+        }
+    }
+
+    pub fn cdr(self) -> Option<Cdr<'a>> {
+        match self {
+            Self::Value(value) => value.cdr(),
+            Self::Items([_, rest @ ..]) => Some(Self::Items(rest)),
+            Self::Items([]) => None,
+        }
+    }
+
+    pub fn nth(self, n: usize) -> Option<&'a Value> {
+        self.iter().nth(n)
+    }
+
+    /// Iterate over the items of a list or a vector.  The tail of an improper
+    /// list like `(1 2 . 3)` is skipped.
+    pub fn iter(self) -> Iter<'a> {
+        let items: &[Value] = match self {
+            Self::Value(Value::List(items) | Value::Vector(items)) => items,
+            Self::Items(items) => items,
+            Self::Value(cons @ Value::Cons(..)) => {
+                return Iter {
+                    items: [].iter(),
+                    tail: Some(cons),
+                }
+            }
+            Self::Value(_) => &[],
+        };
+        Iter {
+            items: items.iter(),
+            tail: None,
+        }
+    }
+
+    /// Return the value of `prop` in a plist, like `plist-get` in Emacs.
+    pub fn plist_get(self, prop: &Value) -> Option<&'a Value> {
+        let mut items = self.iter();
+        while let (Some(key), Some(value)) = (items.next(), items.next()) {
+            if key == prop {
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    /// Return the first item of an alist whose car is `key`, like `assoc` in
+    /// Emacs.
+    pub fn assoc(self, key: &Value) -> Option<&'a Value> {
+        self.iter().find(|item| item.car() == Some(key))
+    }
+
+    /// Return the cdr of the item of an alist whose car is `key`, like
+    /// `alist-get` in Emacs with `equal` as the test.
+    pub fn alist_get(self, key: &Value) -> Option<Cdr<'a>> {
+        self.assoc(key)?.cdr()
+    }
+
+    /// Return the value of `key` in a hash table, an alist or a plist.
+    ///
+    /// Other lists, like `(a b c)`, have no keys, so `b` isn't the value of
+    /// `a` there.
+    pub fn get(self, key: &Value) -> Option<Cdr<'a>> {
+        let items: &[Value] = match self {
+            Self::Value(Value::HashTable { data, .. }) => {
+                return data
+                    .iter()
+                    .find(|(k, _)| k == key)
+                    .map(|(_, value)| Self::Value(value));
+            }
+            Self::Value(Value::List(items)) => items,
+            Self::Items(items) => items,
+            _ => return None,
+        };
+        if is_alist(items) {
+            self.alist_get(key)
+        } else if is_plist(items) {
+            self.plist_get(key).map(Self::Value)
+        } else {
+            None
+        }
+    }
+
+    /// Return the value at a path like `properties."CATEGORY"` or `olp.0`.
+    ///
+    /// Parts of a path are separated by dots.  A number is an index of a list,
+    /// a string literal is a string key, anything else is a symbol key, which
+    /// is also looked up with a leading colon, so `todo` finds `:todo` of a
+    /// plist.  `Err` is returned only when the path itself is invalid.
+    pub fn get_path(self, path: &str) -> Result<Option<Cdr<'a>>> {
+        let mut value = self;
+        for segment in path_segments(path)? {
+            let next = match segment {
+                Segment::Index(n) => value.nth(n).map(Self::Value),
+                Segment::String(key) => value.get(&Value::String(key)),
+                Segment::Symbol(key) => value
+                    .get(&Value::Symbol(format!(":{key}")))
+                    .or_else(|| value.get(&Value::Symbol(key))),
+            };
+            match next {
+                Some(next) => value = next,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(value))
+    }
+}
+
+impl Value {
+    /// Return the first item of a list or a cons, `None` for anything else,
+    /// including `nil`.
+    pub fn car(&self) -> Option<&Value> {
+        match self {
+            Self::List(items) => items.first(),
+            Self::Cons(car, _) => Some(car),
+            _ => None,
+        }
+    }
+
+    /// Return the rest of a list or a cons, `None` for anything else,
+    /// including `nil`.
+    pub fn cdr(&self) -> Option<Cdr<'_>> {
+        match self {
+            Self::List(items) => Some(Cdr::Items(&items[1..])),
+            Self::Cons(_, cdr) => Some(Cdr::Value(cdr)),
+            _ => None,
+        }
+    }
+
+    pub fn nth(&self, n: usize) -> Option<&Value> {
+        Cdr::Value(self).nth(n)
+    }
+
+    /// See [`Cdr::iter`].
+    pub fn iter(&self) -> Iter<'_> {
+        Cdr::Value(self).iter()
+    }
+
+    /// See [`Cdr::plist_get`].
+    pub fn plist_get(&self, prop: &Value) -> Option<&Value> {
+        Cdr::Value(self).plist_get(prop)
+    }
+
+    /// See [`Cdr::assoc`].
+    pub fn assoc(&self, key: &Value) -> Option<&Value> {
+        Cdr::Value(self).assoc(key)
+    }
+
+    /// See [`Cdr::alist_get`].
+    pub fn alist_get(&self, key: &Value) -> Option<Cdr<'_>> {
+        Cdr::Value(self).alist_get(key)
+    }
+
+    /// See [`Cdr::get`].
+    pub fn get(&self, key: &Value) -> Option<Cdr<'_>> {
+        Cdr::Value(self).get(key)
+    }
+
+    /// See [`Cdr::get_path`].
+    pub fn get_path(&self, path: &str) -> Result<Option<Cdr<'_>>> {
+        Cdr::Value(self).get_path(path)
+    }
+}
+
+/// An iterator over the items of a list, see [`Cdr::iter`].
+#[derive(Debug, Clone)]
+pub struct Iter<'a> {
+    items: slice::Iter<'a, Value>,
+    // the cons chain after `items`
+    tail: Option<&'a Value>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.items.next() {
+            return Some(item);
+        }
+        match self.tail.take()? {
+            Value::Cons(car, cdr) => {
+                self.tail = Some(cdr);
+                Some(car)
+            }
+            Value::List(items) => {
+                self.items = items.iter();
+                self.items.next()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a Value {
+    type Item = &'a Value;
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+enum Segment {
+    Index(usize),
+    String(String),
+    Symbol(String),
+}
+
+fn path_segments(path: &str) -> Result<Vec<Segment>> {
+    let mut parser = Parser::new(path);
+    let mut segments = Vec::new();
+    loop {
+        let segment = if parser.ch() == Some('"') {
+            match parser.parse_string().map_err(|kind| parser.error(kind))? {
+                ValueRef::String(s) => Segment::String(s.into_owned()),
+                _ => return Err(parser.error(ErrorKind::InvalidEscape)),
+            }
+        } else {
+            let word = parser.take_while(|c| c != '.');
+            match word.parse() {
+                Ok(n) => Segment::Index(n),
+                Err(_) if !word.is_empty() => Segment::Symbol(word.to_string()),
+                Err(_) => return Err(parser.error(ErrorKind::InvalidSyntax)),
+            }
+        };
+        segments.push(segment);
+        match parser.ch() {
+            None => return Ok(segments),
+            Some('.') => parser.chop(1),
+            Some(_) => return Err(Error::new(ErrorKind::InvalidSyntax, path, parser.cursor)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node() -> Value {
+        r#"(:title "Momentum" :olp ("Physics" "Mechanics")
+            :properties (("CATEGORY" . "physics") ("ID" . "1") ("TAGS" "a" "b"))
+            :refs #s(hash-table test equal data ("cite" [k1 k2])))"#
+            .parse()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_list_accessors() {
+        let list: Value = "(1 2 3)".parse().unwrap();
+        assert_eq!(list.car(), Some(&Value::Integer(1)));
+        assert_eq!(list.cdr().unwrap().to_value().to_string(), "(2 3)");
+        assert_eq!(
+            list.cdr().and_then(Cdr::cdr).and_then(Cdr::car),
+            list.nth(2)
+        );
+        assert_eq!(list.nth(3), None);
+        assert_eq!(Value::Nil.car(), None);
+
+        let dotted: Value = "(1 2 . 3)".parse().unwrap();
+        assert_eq!(dotted.iter().count(), 2);
+        let tail = dotted.cdr().and_then(Cdr::cdr).unwrap();
+        assert_eq!(tail.as_value(), Some(&Value::Integer(3)));
+
+        let sum: i64 = (&list)
+            .into_iter()
+            .filter_map(|n| n.clone().as_integer())
+            .sum();
+        assert_eq!(sum, 6);
+    }
+
+    #[test]
+    fn test_plist_and_alist() {
+        let node = node();
+        let props = node
+            .plist_get(&Value::Symbol(":properties".into()))
+            .unwrap();
+        assert_eq!(
+            props
+                .assoc(&Value::String("ID".into()))
+                .unwrap()
+                .to_string(),
+            r#"("ID" . "1")"#
+        );
+        let category = props.alist_get(&Value::String("CATEGORY".into()));
+        assert_eq!(
+            category.and_then(Cdr::as_value),
+            Some(&Value::String("physics".into()))
+        );
+        let tags = props.alist_get(&Value::String("TAGS".into())).unwrap();
+        assert_eq!(tags.iter().count(), 2);
+        assert_eq!(node.plist_get(&Value::Symbol(":level".into())), None);
+    }
+
+    #[test]
+    fn test_get() {
+        let get = |src: &str, key: &str| {
+            let value: Value = src.parse().unwrap();
+            let key: Value = key.parse().unwrap();
+            value.get(&key).map(|cdr| cdr.to_value().to_string())
+        };
+        assert_eq!(get("((a . 1) (b 2))", "b").as_deref(), Some("(2)"));
+        assert_eq!(get("(:a 1 :b 2)", ":b").as_deref(), Some("2"));
+        assert_eq!(
+            get("#s(hash-table data (\"a\" 1))", "\"a\"").as_deref(),
+            Some("1")
+        );
+        // neither an alist nor a plist
+        assert_eq!(get("(a b c)", "a"), None);
+        assert_eq!(get("(1 2 3)", "1"), None);
+        assert_eq!(get("[a b]", "a"), None);
+    }
+
+    #[test]
+    fn test_get_path() {
+        let node = node();
+        let get = |path| node.get_path(path).unwrap().map(Cdr::to_value);
+        assert_eq!(
+            get(r#"properties."CATEGORY""#),
+            Some(Value::String("physics".into()))
+        );
+        assert_eq!(get("olp.1"), Some(Value::String("Mechanics".into())));
+        assert_eq!(get(r#":refs."cite".1"#), Some(Value::Symbol("k2".into())));
+        assert_eq!(
+            get(r#"properties."TAGS".0"#),
+            Some(Value::String("a".into()))
+        );
+        assert_eq!(get("properties.missing"), None);
+        assert_eq!(get("title.0"), None);
+
+        assert!(node.get_path("olp..1").is_err());
+        assert!(node.get_path(r#""olp"x"#).is_err());
+    }
+}
@@ -0,0 +1,299 @@
+use std::ops::Index;
+use std::vec;
+
+use super::Value;
+
+static NIL: Value = Value::Nil;
+
+/// Build a lisp value with lisp syntax.
+///
+/// ```
+/// use emacsql::{lisp::Value, sexp};
+///
+/// let node = sexp!((:id "1" :level 2 :olp ("A" "B") :pos (10 . -1.5) :done t :todo nil));
+/// assert_eq!(node, r#"(:id "1" :level 2 :olp ("A" "B") :pos (10 . -1.5) :done t :todo nil)"#.parse().unwrap());
+/// assert_eq!(sexp!([a (b . c)]).to_string(), "[a (b . c)]");
+///
+/// // an integer is an `i64`, a character is its code like in Emacs
+/// assert_eq!(sexp!((10000000000 'a')).to_string(), "(10000000000 97)");
+///
+/// // a rust expression in braces is converted with `Value::from`
+/// let title = "Physics";
+/// assert_eq!(sexp!((title {title})), sexp!((title "Physics")));
+/// assert_eq!(sexp!({Value::symbol("org-roam")}).to_string(), "org-roam");
+/// ```
+///
+/// Names are rust identifiers, so a symbol like `org-roam` needs braces.
+/// `true` and `false` are symbols like in lisp, but `{true}` is `t`.
+/// Literals of other types than `i64` and `f64`, like `1u8`, need braces
+/// too.
+#[macro_export]
+macro_rules! sexp {
+    (nil) => {
+        $crate::lisp::Value::Nil
+    };
+    (: $name:ident) => {
+        $crate::lisp::Value::Symbol(::std::concat!(":", ::std::stringify!($name)).to_string())
+    };
+    (- $n:literal) => {
+        $crate::lisp::SexpLiteral::into_value(-$n)
+    };
+    (( $($items:tt)* )) => {
+        $crate::sexp!(@items list [] $($items)*)
+    };
+    ([ $($items:tt)* ]) => {
+        $crate::sexp!(@items vector [] $($items)*)
+    };
+    ({ $value:expr }) => {
+        $crate::lisp::Value::from($value)
+    };
+    ($name:ident) => {
+        $crate::lisp::Value::Symbol(::std::stringify!($name).to_string())
+    };
+    ($value:literal) => {
+        $crate::lisp::SexpLiteral::into_value($value)
+    };
+
+    // the items of a list or a vector are read one by one, since `:name` and
+    // `-1` are two tokens
+    (@items list [$($done:expr,)*]) => {
+        $crate::lisp::Value::list(::std::vec![$($done),*])
+    };
+    (@items vector [$($done:expr,)*]) => {
+        $crate::lisp::Value::Vector(::std::vec![$($done),*])
+    };
+    (@items list [$($done:expr,)*] . $($tail:tt)+) => {
+        $crate::lisp::Value::dotted_list(::std::vec![$($done),*], $crate::sexp!($($tail)+))
+    };
+    (@items $kind:ident [$($done:expr,)*] : $name:ident $($rest:tt)*) => {
+        $crate::sexp!(@items $kind [$($done,)* $crate::sexp!(: $name),] $($rest)*)
+    };
+    (@items $kind:ident [$($done:expr,)*] - $n:literal $($rest:tt)*) => {
+        $crate::sexp!(@items $kind [$($done,)* $crate::sexp!(- $n),] $($rest)*)
+    };
+    (@items $kind:ident [$($done:expr,)*] $item:tt $($rest:tt)*) => {
+        $crate::sexp!(@items $kind [$($done,)* $crate::sexp!($item),] $($rest)*)
+    };
+}
+
+/// A literal of [`sexp!`].
+///
+/// It's implemented for `i64`, but not for other integers, so an integer
+/// literal without a suffix is an `i64` instead of an `i32`.
+#[doc(hidden)]
+pub trait SexpLiteral {
+    fn into_value(self) -> Value;
+}
+
+macro_rules! some_literal_impls {
+    ($( $for:ty ),*) => {
+        $(
+            impl SexpLiteral for $for {
+                fn into_value(self) -> Value {
+                    Value::from(self)
+                }
+            }
+        )*
+    };
+}
+
+some_literal_impls![i64, f64, &str, char];
+
+impl Value {
+    pub fn symbol(name: impl Into<String>) -> Value {
+        Self::Symbol(name.into())
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Self::String(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Self::String(s)
+    }
+}
+
+/// A character is its code, like `?a` is `97` in Emacs.
+impl From<char> for Value {
+    fn from(c: char) -> Self {
+        Self::Integer(u32::from(c).into())
+    }
+}
+
+/// `true` is `t` and `false` is `nil`.
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        if b {
+            Self::symbol("t")
+        } else {
+            Self::Nil
+        }
+    }
+}
+
+macro_rules! some_integer_impls {
+    ($( $for:ident ),*) => {
+        $(
+            impl From<$for> for Value {
+                fn from(n: $for) -> Self {
+                    Self::Integer(n.into())
+                }
+            }
+        )*
+    };
+}
+
+some_integer_impls![i8, i16, i32, i64, u8, u16, u32];
+
+macro_rules! some_real_impls {
+    ($( $for:ident ),*) => {
+        $(
+            impl From<$for> for Value {
+                fn from(n: $for) -> Self {
+                    Self::Real(n.into())
+                }
+            }
+        )*
+    };
+}
+
+some_real_impls![f32, f64];
+
+/// `None` is `nil`.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Self::Nil, Into::into)
+    }
+}
+
+/// A proper list, the empty vector is `nil`.
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Self {
+        items.into_iter().collect()
+    }
+}
+
+/// Collect items into a proper list.
+impl<T: Into<Value>> FromIterator<T> for Value {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::list(iter.into_iter().map(Into::into).collect())
+    }
+}
+
+/// Return the item of a list or a vector like `nth` in Emacs, `nil` when
+/// there's no such item.
+impl Index<usize> for Value {
+    type Output = Value;
+
+    fn index(&self, n: usize) -> &Value {
+        self.nth(n).unwrap_or(&NIL)
+    }
+}
+
+/// An iterator over the items of an owned list, see [`Value::iter`].
+#[derive(Debug, Clone)]
+pub struct IntoIter {
+    items: vec::IntoIter<Value>,
+    // the cons chain after `items`
+    tail: Option<Value>,
+}
+
+impl Iterator for IntoIter {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.items.next() {
+            return Some(item);
+        }
+        match self.tail.take()? {
+            Value::Cons(car, cdr) => {
+                self.tail = Some(*cdr);
+                Some(*car)
+            }
+            Value::List(items) => {
+                self.items = items.into_iter();
+                self.items.next()
+            }
+            _ => None,
+        }
+    }
+}
+
+impl IntoIterator for Value {
+    type Item = Value;
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        let (items, tail) = match self {
+            Self::List(items) | Self::Vector(items) => (items, None),
+            cons @ Self::Cons(..) => (Vec::new(), Some(cons)),
+            _ => (Vec::new(), None),
+        };
+        IntoIter {
+            items: items.into_iter(),
+            tail,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sexp_macro() {
+        assert_eq!(
+            sexp!((a "b" 1 . 2)),
+            Value::Cons(
+                Box::new(Value::symbol("a")),
+                Box::new(Value::Cons(
+                    Box::new(Value::String("b".into())),
+                    Box::new(Value::Cons(
+                        Box::new(Value::Integer(1)),
+                        Box::new(Value::Integer(2))
+                    ))
+                ))
+            )
+        );
+        assert_eq!(sexp!(()), Value::Nil);
+        assert_eq!(
+            sexp!((:pos 10000000000 :min -9223372036854775808 :char 'ö')),
+            sexp!((:pos {10_000_000_000i64} :min {i64::MIN} :char 246))
+        );
+        assert_eq!(sexp!((1 . (2 3))), sexp!((1 2 3)));
+        assert_eq!(sexp!((a b . nil)), sexp!((a b)));
+        assert_eq!(
+            sexp!([:k -1 -2.5 t {false} [x]]).to_string(),
+            "[:k -1 -2.5 t nil [x]]"
+        );
+        let olp = vec!["A", "B"];
+        let level: Option<i64> = None;
+        assert_eq!(
+            sexp!((:olp {olp} :level {level} :pos {40 + 2})).to_string(),
+            r#"(:olp ("A" "B") :level nil :pos 42)"#
+        );
+    }
+
+    #[test]
+    fn test_index_and_iterators() {
+        let list = sexp!((1 "two" (3)));
+        assert_eq!(list[1], Value::from("two"));
+        assert_eq!(list[2][0], Value::from(3));
+        assert_eq!(list[3], Value::Nil);
+        assert_eq!(Value::from(1)[0], Value::Nil);
+
+        let items: Vec<Value> = sexp!((1 2 . 3)).into_iter().collect();
+        assert_eq!(items, [Value::from(1), Value::from(2)]);
+        let doubled: Value = sexp!([1 2 3])
+            .into_iter()
+            .filter_map(Value::as_integer)
+            .map(|n| n * 2)
+            .collect();
+        assert_eq!(doubled, sexp!((2 4 6)));
+        assert_eq!(Vec::<i64>::new().into_iter().collect::<Value>(), Value::Nil);
+    }
+}
@@ -0,0 +1,332 @@
+use std::fmt::{self, Display, Write};
+
+use super::Value;
+
+/// Return the text that `prin1` in Emacs would produce for a given value.
+///
+/// The result can be read back with [`super::parse_string`] or with `read` in
+/// Emacs.
+pub fn to_lisp_string(value: &Value) -> String {
+    value.to_string()
+}
+
+impl Value {
+    /// Print this value the same way as `prin1` in Emacs does.
+    pub fn to_lisp_string(&self) -> String {
+        to_lisp_string(self)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::String(s) => write_string(s, f),
+            Value::Symbol(s) => write_symbol(s, f),
+            Value::Integer(n) => write!(f, "{n}"),
+            Value::BigInt(n) => f.write_str(n),
+            Value::Real(n) => write_float(*n, f),
+            Value::Nil => f.write_str("nil"),
+            Value::List(items) if items.is_empty() => f.write_str("nil"),
+            Value::List(items) => {
+                if let Some((prefix, quoted)) = quote_shorthand(items) {
+                    return write!(f, "{prefix}{quoted}");
+                }
+                f.write_char('(')?;
+                write_items(items, f)?;
+                f.write_char(')')
+            }
+            Value::Vector(items) => {
+                f.write_char('[')?;
+                write_items(items, f)?;
+                f.write_char(']')
+            }
+            Value::Cons(car, cdr) => {
+                write!(f, "({car}")?;
+                let mut tail = cdr.as_ref();
+                loop {
+                    match tail {
+                        Value::Cons(car, cdr) => {
+                            write!(f, " {car}")?;
+                            tail = cdr;
+                        }
+                        Value::List(items) => {
+                            f.write_char(' ')?;
+                            write_items(items, f)?;
+                            return f.write_char(')');
+                        }
+                        Value::Nil => return f.write_char(')'),
+                        tail => return write!(f, " . {tail})"),
+                    }
+                }
+            }
+            Value::HashTable { test, data } => {
+                f.write_str("#s(hash-table ")?;
+                if let Some(test) = test {
+                    f.write_str("test ")?;
+                    write_symbol(test, f)?;
+                    f.write_char(' ')?;
+                }
+                f.write_str("data (")?;
+                for (i, (key, val)) in data.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(' ')?;
+                    }
+                    write!(f, "{key} {val}")?;
+                }
+                f.write_str("))")
+            }
+            Value::Record(items) => {
+                f.write_str("#s(")?;
+                write_items(items, f)?;
+                f.write_char(')')
+            }
+            Value::Unibyte(bytes) => write_unibyte(bytes, f),
+            Value::Propertized(text, props) => {
+                f.write_str("#(")?;
+                write_string(text, f)?;
+                for prop in props {
+                    write!(f, " {} {} {}", prop.start, prop.end, prop.plist)?;
+                }
+                f.write_char(')')
+            }
+        }
+    }
+}
+
+/// If a list is `(quote x)` or alike, return the prefix which `prin1` uses
+/// instead, like `'`, and the quoted value.
+fn quote_shorthand(items: &[Value]) -> Option<(&'static str, &Value)> {
+    let [Value::Symbol(head), quoted] = items else {
+        return None;
+    };
+    let prefix = match head.as_str() {
+        "quote" => "'",
+        "function" => "#'",
+        "`" => "`",
+        "," => ",",
+        ",@" => ",@",
+        _ => return None,
+    };
+    Some((prefix, quoted))
+}
+
+fn write_items(items: &[Value], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for (i, item) in items.iter().enumerate() {
+        if i != 0 {
+            f.write_char(' ')?;
+        }
+        write!(f, "{item}")?;
+    }
+    Ok(())
+}
+
+/// Write a string literal, only `"` and `\` need escaping for the Emacs reader.
+fn write_string(s: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_char('"')?;
+    for ch in s.chars() {
+        if ch == '"' || ch == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(ch)?;
+    }
+    f.write_char('"')
+}
+
+/// Write a unibyte string like `prin1` does: bytes above 127 are written as
+/// octal escapes, so the result is always ASCII.
+fn write_unibyte(bytes: &[u8], f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_char('"')?;
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+            0..=127 => f.write_char(byte as char)?,
+            _ => write!(f, "\\{byte:03o}")?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Write a symbol name, escaping characters which the reader would treat
+/// specially.
+///
+/// A name that would be read as a number (like `1` or `-2.5`) is prefixed with
+/// `\`, and the symbol with an empty name is printed as `##`.
+fn write_symbol(name: &str, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    if name.is_empty() {
+        return f.write_str("##");
+    }
+    if looks_like_number(name) {
+        f.write_char('\\')?;
+    }
+    for (i, ch) in name.chars().enumerate() {
+        if needs_escape_in_symbol(ch) || (i == 0 && (ch == '?' || ch == '.')) {
+            f.write_char('\\')?;
+        }
+        f.write_char(ch)?;
+    }
+    Ok(())
+}
+
+fn needs_escape_in_symbol(ch: char) -> bool {
+    ch.is_whitespace()
+        || ch.is_control()
+        || matches!(
+            ch,
+            '"' | '\\' | '\'' | ';' | '#' | '(' | ')' | '[' | ']' | ',' | '`'
+        )
+}
+
+/// Return true, if the Emacs reader would read `s` as an integer or a float.
+fn looks_like_number(s: &str) -> bool {
+    !matches!(super::parse_number(s), Ok(None))
+}
+
+/// Write a float like `prin1` in Emacs.
+///
+/// Emacs prints the shortest representation which reads back to the same
+/// float, in the style of `%g` with at least 15 digits of precision, and
+/// always adds `.0` when the result would look like an integer.
+fn write_float(n: f64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let sign = if n.is_sign_negative() { "-" } else { "" };
+    if n.is_nan() {
+        return write!(f, "{sign}0.0e+NaN");
+    }
+    if n.is_infinite() {
+        return write!(f, "{sign}1.0e+INF");
+    }
+
+    let sci = format!("{:e}", n.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let digits: String = mantissa.chars().filter(char::is_ascii_digit).collect();
+    let precision = std::cmp::max(15, digits.len() as i32);
+
+    f.write_str(sign)?;
+    if exp < -4 || exp >= precision {
+        let exp_sign = if exp < 0 { '-' } else { '+' };
+        write!(f, "{mantissa}e{exp_sign}{:02}", exp.abs())
+    } else if exp >= 0 {
+        let int_len = exp as usize + 1;
+        if digits.len() <= int_len {
+            write!(f, "{digits}{}.0", "0".repeat(int_len - digits.len()))
+        } else {
+            write!(f, "{}.{}", &digits[..int_len], &digits[int_len..])
+        }
+    } else {
+        write!(f, "0.{}{digits}", "0".repeat((-exp - 1) as usize))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lisp::parse_string;
+
+    // every source which the parser tests in `lisp.rs` expect to be parsed
+    const PARSER_CASES: &[&str] = &[
+        "   \"ur son!\"",
+        "(\"a\" \"b\")",
+        "(1 2 3 4.5 -6.7)",
+        "(1 2 3 4 (5 6 [ 7 8 ]))",
+        "(1 2 3 4 . 7)",
+        "(1 . 2)",
+        " myman1 ",
+        " 2drots ",
+        "\" baba\\\"papa \" ",
+    ];
+
+    #[test]
+    fn test_round_trip_parser_cases() {
+        for src in PARSER_CASES {
+            let val = parse_string(src).unwrap();
+            let printed = to_lisp_string(&val);
+            assert_eq!(parse_string(&printed), Ok(val), "printed as {printed}");
+        }
+    }
+
+    #[test]
+    fn test_round_trip_reader_syntax() {
+        let cases = [
+            "'a",
+            "(#'car `(a ,b ,@c))",
+            "#s(hash-table test equal data (\"ID\" \"x\" a 1))",
+            "#s(org-roam-node \"id\" 1)",
+            "#(\"text\" 0 3 (face bold))",
+            r"(a\ b \1 \-2.5 ## \?x)",
+            "(1.0e+INF -1.0e+INF 0.0e+NaN -0.0 123456789012345678901234)",
+        ];
+        for src in cases {
+            let val = parse_string(src).unwrap();
+            assert_eq!(to_lisp_string(&val), src);
+        }
+    }
+
+    #[test]
+    fn test_print_string() {
+        let val = Value::String("say \"hi\" \\o/\nbye".into());
+        assert_eq!(val.to_string(), "\"say \\\"hi\\\" \\\\o/\nbye\"");
+    }
+
+    #[test]
+    fn test_print_unibyte_string() {
+        let val = parse_string(r#""caf\351 \x41\377""#).unwrap();
+        assert_eq!(val, Value::Unibyte(b"caf\xe9 A\xff".to_vec()));
+        assert_eq!(val.to_string(), r#""caf\351 A\377""#);
+    }
+
+    #[test]
+    fn test_print_list_and_cons() {
+        let val = Value::List(vec![
+            Value::Cons(
+                Box::new(Value::String("ID".into())),
+                Box::new(Value::String("x".into())),
+            ),
+            Value::Symbol("a".into()),
+            Value::Nil,
+        ]);
+        assert_eq!(val.to_string(), "((\"ID\" . \"x\") a nil)");
+    }
+
+    #[test]
+    fn test_print_improper_list_and_vector() {
+        for src in ["(1 2 3 . 4)", "[1 (2 . 3) [] nil]", "((\"ID\" . \"x\"))"] {
+            let val = parse_string(src).unwrap();
+            assert_eq!(to_lisp_string(&val), src);
+        }
+    }
+
+    #[test]
+    fn test_print_symbol_escapes() {
+        let sym = |s: &str| Value::Symbol(s.into()).to_string();
+        assert_eq!(sym("org-roam"), "org-roam");
+        assert_eq!(sym(":key"), ":key");
+        assert_eq!(sym("a b"), "a\\ b");
+        assert_eq!(sym("(x)"), "\\(x\\)");
+        assert_eq!(sym("?a"), "\\?a");
+        assert_eq!(sym("1"), "\\1");
+        assert_eq!(sym("-2.5"), "\\-2.5");
+        assert_eq!(sym("1e5"), "\\1e5");
+        assert_eq!(sym("2drots"), "2drots");
+        assert_eq!(sym("-"), "-");
+        assert_eq!(sym(""), "##");
+    }
+
+    #[test]
+    fn test_print_float() {
+        let float = |n: f64| Value::Real(n).to_string();
+        assert_eq!(float(1.0), "1.0");
+        assert_eq!(float(-6.7), "-6.7");
+        assert_eq!(float(0.0), "0.0");
+        assert_eq!(float(-0.0), "-0.0");
+        assert_eq!(float(100.0), "100.0");
+        assert_eq!(float(0.0001), "0.0001");
+        assert_eq!(float(0.00001), "1e-05");
+        assert_eq!(float(1.5e-7), "1.5e-07");
+        assert_eq!(float(1e14), "100000000000000.0");
+        assert_eq!(float(1e15), "1e+15");
+        assert_eq!(float(0.1 + 0.2), "0.30000000000000004");
+        assert_eq!(float(f64::INFINITY), "1.0e+INF");
+        assert_eq!(float(f64::NEG_INFINITY), "-1.0e+INF");
+        assert_eq!(float(f64::NAN), "0.0e+NaN");
+    }
+}
@@ -0,0 +1,316 @@
+use std::fmt::{self, Display};
+use std::io::{self, Read};
+
+use super::{Error, Parser, Value};
+
+// how many bytes `Reader` reads at once, at least
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// A place in a lisp source.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    /// the byte offset from the start of the source
+    pub offset: usize,
+    /// the line, starting from 1
+    pub line: usize,
+    /// the column in characters, starting from 1
+    pub column: usize,
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Self {
+            offset: 0,
+            line: 1,
+            column: 1,
+        }
+    }
+}
+
+impl Position {
+    /// Return the position after `text` which starts at this position.
+    fn advance(mut self, text: &str) -> Self {
+        self.offset += text.len();
+        match text.rsplit_once('\n') {
+            Some((before, after)) => {
+                self.line += before.matches('\n').count() + 1;
+                self.column = after.chars().count() + 1;
+            }
+            None => self.column += text.chars().count(),
+        }
+        self
+    }
+
+    /// Move an error of the text which starts at this position, so it's
+    /// relative to the whole source.
+    fn shift(self, mut err: Error) -> Error {
+        if err.line == 1 {
+            err.column += self.column - 1;
+        }
+        err.line += self.line - 1;
+        err.offset += self.offset;
+        err
+    }
+}
+
+/// A top-level form and the place where it starts.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Form {
+    pub value: Value,
+    pub start: Position,
+}
+
+/// Return an iterator over all top-level forms of `src`.
+///
+/// Unlike [`super::parse_string`], it doesn't ignore forms after the first
+/// one.  The iterator stops after the first error.
+pub fn read_all(src: &str) -> Forms<'_> {
+    Forms {
+        parser: Parser::new(src),
+        pos: Position::default(),
+        failed: false,
+    }
+}
+
+/// An iterator over the forms of a string, see [`read_all`].
+pub struct Forms<'a> {
+    parser: Parser<'a>,
+    pos: Position,
+    failed: bool,
+}
+
+impl Iterator for Forms<'_> {
+    type Item = Result<Form, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        match self.parser.next_form()? {
+            Ok((offset, value)) => {
+                let skipped = &self.parser.src[self.pos.offset..offset];
+                self.pos = self.pos.advance(skipped);
+                Some(Ok(Form {
+                    value: value.into_owned(),
+                    start: self.pos,
+                }))
+            }
+            Err(err) => {
+                self.failed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// An error of [`Reader`].
+#[derive(Debug)]
+pub enum ReadError {
+    Io(io::Error),
+    Syntax(Error),
+}
+
+impl Display for ReadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "can't read a lisp source: {err}"),
+            Self::Syntax(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::Syntax(err) => Some(err),
+        }
+    }
+}
+
+impl From<io::Error> for ReadError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<Error> for ReadError {
+    fn from(err: Error) -> Self {
+        Self::Syntax(err)
+    }
+}
+
+/// An iterator over the top-level forms of an [`io::Read`], like an `.el` file
+/// or a dump of `prin1`.
+///
+/// Only the text of the form which is being read is kept in memory.  The
+/// iterator stops after the first error.
+pub struct Reader<R> {
+    read: R,
+    // the text which isn't read yet, it starts at `start`
+    buf: String,
+    start: Position,
+    // the bytes after `buf` which aren't a whole UTF-8 character yet
+    bytes: Vec<u8>,
+    chunk_size: usize,
+    eof: bool,
+    failed: bool,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(read: R) -> Self {
+        Self {
+            read,
+            buf: String::new(),
+            start: Position::default(),
+            bytes: Vec::new(),
+            chunk_size: CHUNK_SIZE,
+            eof: false,
+            failed: false,
+        }
+    }
+
+    fn read_form(&mut self) -> Result<Option<Form>, ReadError> {
+        loop {
+            let mut parser = Parser::new(&self.buf);
+            let form = parser
+                .next_form()
+                .map(|form| form.map(|(offset, value)| (offset, value.into_owned())));
+            let cursor = parser.cursor;
+            // a form at the end of the buffer can be a prefix of a longer
+            // one, like `12` of `123`, so it's read again with more text
+            match form {
+                Some(Ok((offset, value))) if self.eof || cursor < self.buf.len() => {
+                    let start = self.start.advance(&self.buf[..offset]);
+                    self.start = start.advance(&self.buf[offset..cursor]);
+                    self.buf.drain(..cursor);
+                    return Ok(Some(Form { value, start }));
+                }
+                None if self.eof => return Ok(None),
+                // an error in the last token, like `#s` of `#s(...)`, can
+                // go away with more text, one followed by a space can't
+                Some(Err(err)) if self.eof || has_space_after(&self.buf, cursor) => {
+                    return Err(self.start.shift(err).into())
+                }
+                _ => self.fill()?,
+            }
+        }
+    }
+
+    /// Append the next chunk of the input to the buffer.
+    fn fill(&mut self) -> io::Result<()> {
+        // the buffer is at least doubled, so a long form is parsed again only
+        // a few times
+        let len = self.buf.len().max(self.chunk_size);
+        let old_len = self.bytes.len();
+        (&mut self.read)
+            .take(len as u64)
+            .read_to_end(&mut self.bytes)?;
+        self.eof = self.bytes.len() == old_len;
+
+        let valid = match std::str::from_utf8(&self.bytes) {
+            Ok(text) => text.len(),
+            Err(err) if err.error_len().is_none() && !self.eof => err.valid_up_to(),
+            Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+        };
+        let text = std::str::from_utf8(&self.bytes[..valid]).unwrap();
+        self.buf.push_str(text);
+        self.bytes.drain(..valid);
+        Ok(())
+    }
+}
+
+/// Return true, if there's a space after the byte `cursor` of `buf`.
+fn has_space_after(buf: &str, cursor: usize) -> bool {
+    buf.get(cursor..)
+        .is_some_and(|rest| rest.contains(char::is_whitespace))
+}
+
+impl<R: Read> Iterator for Reader<R> {
+    type Item = Result<Form, ReadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let form = self.read_form();
+        self.failed = form.is_err();
+        form.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lisp::ErrorKind;
+
+    const SRC: &str = r#";;; init.el
+(setq org-roam-directory "~/örg")
+
+(org-roam-db-autosync-mode) 12
+  [a "b c"] 'x"#;
+
+    fn starts(forms: &[Form]) -> Vec<(usize, usize, usize)> {
+        forms
+            .iter()
+            .map(|form| (form.start.offset, form.start.line, form.start.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_read_all() {
+        let forms = read_all(SRC).collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(forms.len(), 5);
+        assert_eq!(forms[2].value, Value::Integer(12));
+        assert_eq!(forms[4].value.to_string(), "'x");
+        assert_eq!(
+            starts(&forms),
+            [(12, 2, 1), (48, 4, 1), (76, 4, 29), (81, 5, 3), (91, 5, 13)]
+        );
+        assert_eq!(read_all(" ;; only a comment").count(), 0);
+    }
+
+    #[test]
+    fn test_read_all_stops_on_error() {
+        let mut forms = read_all("a (b\n c");
+        assert!(forms.next().unwrap().is_ok());
+        let err = forms.next().unwrap().unwrap_err();
+        assert_eq!((err.kind, err.line), (ErrorKind::UnbalancedExpr, 2));
+        assert!(forms.next().is_none());
+    }
+
+    #[test]
+    fn test_reader_in_small_chunks() {
+        for chunk_size in [1, 2, 3, 7, CHUNK_SIZE] {
+            let mut reader = Reader::new(SRC.as_bytes());
+            reader.chunk_size = chunk_size;
+            let forms = reader.collect::<Result<Vec<_>, _>>().unwrap();
+            let expected = read_all(SRC).collect::<Result<Vec<_>, _>>().unwrap();
+            assert_eq!(forms, expected, "chunks of {chunk_size} bytes");
+        }
+    }
+
+    #[test]
+    fn test_reader_errors() {
+        let mut reader = Reader::new("(a)\n(b \"c\" . d e)".as_bytes());
+        reader.chunk_size = 4;
+        assert!(reader.next().unwrap().is_ok());
+        let Some(Err(ReadError::Syntax(err))) = reader.next() else {
+            panic!("expected a syntax error");
+        };
+        assert_eq!((err.kind, err.line), (ErrorKind::UnexpectedDot, 2));
+        assert!(err.offset > 4 && err.column > 1);
+        assert!(reader.next().is_none());
+
+        // the error is found without reading the rest of the input
+        let src = format!("(a)\n(b . c d)\n{}", "(e \"f\")\n".repeat(10_000));
+        let mut reader = Reader::new(src.as_bytes());
+        reader.chunk_size = 16;
+        assert!(reader.next().unwrap().is_ok());
+        assert!(matches!(reader.next(), Some(Err(ReadError::Syntax(_)))));
+        assert!(reader.buf.len() < 64, "{} bytes are read", reader.buf.len());
+
+        let mut reader = Reader::new(&b"(a \xff)"[..]);
+        assert!(matches!(reader.next(), Some(Err(ReadError::Io(_)))));
+    }
+}
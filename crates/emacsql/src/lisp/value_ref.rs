@@ -0,0 +1,160 @@
+use std::borrow::Cow;
+
+use super::{TextProperty, Value};
+
+/// A lisp value which borrows strings and symbols from the source it's read
+/// from, see [`super::parse_borrowed`].
+///
+/// Only strings and symbols with escapes are copied.  The variants mean the
+/// same as the ones of [`Value`].
+#[derive(PartialEq, Clone, Debug)]
+pub enum ValueRef<'a> {
+    String(Cow<'a, str>),
+    Cons(Box<ValueRef<'a>>, Box<ValueRef<'a>>),
+    Integer(i64),
+    BigInt(String),
+    Real(f64),
+    Symbol(Cow<'a, str>),
+    List(Vec<ValueRef<'a>>),
+    Vector(Vec<ValueRef<'a>>),
+    Nil,
+    HashTable {
+        test: Option<Cow<'a, str>>,
+        data: Vec<(ValueRef<'a>, ValueRef<'a>)>,
+    },
+    Record(Vec<ValueRef<'a>>),
+    Propertized(Cow<'a, str>, Vec<TextProperty<ValueRef<'a>>>),
+    /// raw bytes are always written with escapes, so they're never borrowed
+    Unibyte(Vec<u8>),
+}
+
+impl<'a> ValueRef<'a> {
+    /// See [`Value::dotted_list`].
+    pub fn dotted_list(mut items: Vec<Self>, tail: Self) -> Self {
+        match tail {
+            Self::Nil => Self::list(items),
+            Self::List(rest) => {
+                items.extend(rest);
+                Self::List(items)
+            }
+            tail => items
+                .into_iter()
+                .rev()
+                .fold(tail, |cdr, car| Self::Cons(Box::new(car), Box::new(cdr))),
+        }
+    }
+
+    /// See [`Value::list`].
+    pub fn list(items: Vec<Self>) -> Self {
+        if items.is_empty() {
+            Self::Nil
+        } else {
+            Self::List(items)
+        }
+    }
+
+    pub fn as_symbol(self) -> Option<Cow<'a, str>> {
+        match self {
+            Self::Symbol(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Copy everything borrowed from the source.
+    pub fn into_owned(self) -> Value {
+        let all = |items: Vec<Self>| items.into_iter().map(Self::into_owned).collect();
+        match self {
+            Self::String(s) => Value::String(s.into_owned()),
+            Self::Cons(car, cdr) => {
+                Value::Cons(Box::new(car.into_owned()), Box::new(cdr.into_owned()))
+            }
+            Self::Integer(n) => Value::Integer(n),
+            Self::BigInt(n) => Value::BigInt(n),
+            Self::Real(n) => Value::Real(n),
+            Self::Symbol(s) => Value::Symbol(s.into_owned()),
+            Self::List(items) => Value::List(all(items)),
+            Self::Vector(items) => Value::Vector(all(items)),
+            Self::Nil => Value::Nil,
+            Self::HashTable { test, data } => Value::HashTable {
+                test: test.map(Cow::into_owned),
+                data: data
+                    .into_iter()
+                    .map(|(k, v)| (k.into_owned(), v.into_owned()))
+                    .collect(),
+            },
+            Self::Record(items) => Value::Record(all(items)),
+            Self::Propertized(s, props) => Value::Propertized(
+                s.into_owned(),
+                props
+                    .into_iter()
+                    .map(|prop| TextProperty {
+                        start: prop.start,
+                        end: prop.end,
+                        plist: prop.plist.into_owned(),
+                    })
+                    .collect(),
+            ),
+            Self::Unibyte(bytes) => Value::Unibyte(bytes),
+        }
+    }
+}
+
+impl From<ValueRef<'_>> for Value {
+    fn from(value: ValueRef<'_>) -> Self {
+        value.into_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lisp::{parse_borrowed, parse_string};
+
+    #[test]
+    fn test_parse_borrowed() {
+        let src = r#"(("ID" . "1") ("TITLE" . "say \"hi\"") :tag a\ b)"#;
+        let ValueRef::List(items) = parse_borrowed(src).unwrap() else {
+            panic!("expected a list");
+        };
+        let ValueRef::Cons(id, _) = &items[0] else {
+            panic!("expected a cons");
+        };
+        assert!(matches!(**id, ValueRef::String(Cow::Borrowed("ID"))));
+        let ValueRef::Cons(_, title) = &items[1] else {
+            panic!("expected a cons");
+        };
+        assert!(matches!(&**title, ValueRef::String(Cow::Owned(s)) if s == "say \"hi\""));
+        assert!(matches!(items[2], ValueRef::Symbol(Cow::Borrowed(":tag"))));
+        assert!(matches!(&items[3], ValueRef::Symbol(Cow::Owned(s)) if s == "a b"));
+    }
+
+    #[test]
+    fn test_into_owned() {
+        let src = r#"(1 "a" [b 2.5] (c . d) #s(hash-table data (k v)) #("x" 0 1 (face bold)))"#;
+        let borrowed = parse_borrowed(src).unwrap();
+        assert_eq!(borrowed.into_owned(), parse_string(src).unwrap());
+    }
+
+    #[test]
+    fn test_row_get_borrowed() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE links (properties);
+INSERT INTO links VALUES ('(:outline ("A" "B"))');
+"#,
+        )
+        .unwrap();
+        let len = conn
+            .query_row("SELECT properties FROM links", [], |row| {
+                let props = crate::Row::from(row).get_borrowed("properties")?;
+                let ValueRef::List(items) = props else {
+                    panic!("expected a list");
+                };
+                assert_eq!(items[0], ValueRef::Symbol(":outline".into()));
+                Ok(items.len())
+            })
+            .unwrap();
+        assert_eq!(len, 2);
+    }
+}
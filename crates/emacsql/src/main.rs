@@ -1,45 +1,330 @@
-use emacsql::{Error, FromRow, QueryAs, Result, Row};
-use rusqlite::Connection;
-
-#[derive(Debug)]
-struct Repository {
-    id: String,
-    class: String,
-    forge: Option<String>,
-    forge_id: Option<String>,
-    sparse_p: Option<bool>,
-    stars: Option<usize>,
-}
-
-impl FromRow for Repository {
-    fn try_from_row(row: &Row) -> Result<Self> {
-        let rep = Self {
-            id: row.get("id")?,
-            class: row.get("class")?,
-            forge: row.get("forge")?,
-            forge_id: row.get("forge_id")?,
-            sparse_p: row.get("sparse_p")?,
-            stars: row.get("stars")?,
+//! Look into any EmacSQL database: the ones of org-roam, forge and others.
+
+use std::error::Error;
+use std::fmt::Write as _;
+use std::process::ExitCode;
+use std::sync::Arc;
+
+use emacsql::compiler;
+use emacsql::dynamic::{self, DynamicRow};
+use emacsql::json::JsonOptions;
+use emacsql::lisp::{self, Value};
+use emacsql::schema::ColumnTypes;
+use emacsql::Row;
+use rusqlite::{Connection, OpenFlags};
+
+const USAGE: &str = "\
+Usage: emacsql [OPTIONS] DATABASE [QUERY [ARGS...]]
+
+Without QUERY, list the tables of DATABASE and their columns.
+
+QUERY is SQL or, when it starts with `[`, an EmacSQL statement like
+`[:select * :from nodes :where (= id $s1)]`.  ARGS are lisp values of its
+parameters, or of `?` placeholders of SQL.
+
+Options:
+  -f, --format FORMAT  print rows as `table` (the default), `json` or `sexp`
+  -w, --write          open DATABASE for writing, it's read-only otherwise
+  -h, --help           print this help";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Format {
+    Table,
+    Json,
+    Sexp,
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    database: String,
+    query: Option<String>,
+    args: Vec<String>,
+    format: Format,
+    write: bool,
+}
+
+/// Parse the command line arguments, return `None` when the help is asked.
+fn parse_args(mut argv: impl Iterator<Item = String>) -> Result<Option<Args>, String> {
+    let mut format = Format::Table;
+    let mut write = false;
+    let mut positional = Vec::new();
+    while let Some(arg) = argv.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "-w" | "--write" => write = true,
+            "-f" | "--format" => {
+                format = match argv.next().as_deref() {
+                    Some("table") => Format::Table,
+                    Some("json") => Format::Json,
+                    Some("sexp") => Format::Sexp,
+                    Some(other) => return Err(format!("unknown format `{other}`")),
+                    None => return Err(format!("{arg} needs a format")),
+                }
+            }
+            _ => positional.push(arg),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let database = positional.next().ok_or("DATABASE isn't given")?;
+    Ok(Some(Args {
+        database,
+        query: positional.next(),
+        args: positional.collect(),
+        format,
+        write,
+    }))
+}
+
+/// Print the tables of a database and the names of their columns.
+fn list_tables(conn: &Connection) -> rusqlite::Result<String> {
+    let tables = conn
+        .prepare("SELECT name FROM sqlite_master WHERE type = 'table' ORDER BY name")?
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let mut out = String::new();
+    for table in tables {
+        let columns = stmt
+            .query_map([&table], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        writeln!(out, "{table} ({})", columns.join(", ")).unwrap();
+    }
+    Ok(out)
+}
+
+// the names of columns and decoded rows
+type Table = (Arc<[String]>, Vec<DynamicRow>);
+
+/// Run a query and return its decoded rows.
+fn query(conn: &Connection, src: &str, args: &[String]) -> Result<Table, Box<dyn Error>> {
+    let args = args
+        .iter()
+        .map(|arg| lisp::parse_string(arg))
+        .collect::<lisp::Result<Vec<_>>>()?;
+    let (sql, params) = if src.trim_start().starts_with('[') {
+        let query = compiler::compile_str(src, &args)?;
+        (query.sql, query.params)
+    } else {
+        let params = args.into_iter().map(emacsql::value::Value::Lisp).collect();
+        (src.to_string(), params)
+    };
+
+    let mut stmt = conn.prepare(&sql)?;
+    let types = ColumnTypes::of_statement(conn, &stmt)?;
+    let columns = dynamic::column_names(&stmt);
+    let mut rows = stmt.query(rusqlite::params_from_iter(&params))?;
+    let mut decoded = Vec::new();
+    while let Some(row) = rows.next()? {
+        let row = Row::from(row).column_types(&types);
+        decoded.push(DynamicRow::from_row(&row, columns.clone())?);
+    }
+    Ok((columns, decoded))
+}
+
+/// Print rows as a table with aligned columns, strings are printed without
+/// quotes.  A statement without columns, like `UPDATE`, prints nothing.
+fn format_table(columns: &[String], rows: &[DynamicRow]) -> String {
+    if columns.is_empty() {
+        return String::new();
+    }
+    let cell = |value: &emacsql::Value| {
+        let text = match Value::from(value.clone()) {
+            Value::String(s) => s,
+            value => value.to_string(),
         };
-        Ok(rep)
+        text.replace('\n', "\\n")
+    };
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.values().iter().map(cell).collect())
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.chars().count()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+
+    let mut out = String::new();
+    let mut write_line = |line: &mut dyn Iterator<Item = String>| {
+        let line: Vec<String> = line.collect();
+        writeln!(out, "{}", line.join(" | ").trim_end()).unwrap();
+    };
+    write_line(&mut columns.iter().zip(&widths).map(|(c, w)| format!("{c:w$}")));
+    write_line(&mut widths.iter().map(|w| "-".repeat(*w)));
+    for row in &cells {
+        write_line(&mut row.iter().zip(&widths).map(|(c, w)| format!("{c:w$}")));
     }
+    out
 }
 
-fn main() {
-    let filename = "c:/Users/hrams/AppData/Roaming/.emacs.d/forge-database.sqlite";
-    let conn = Connection::open(filename).unwrap();
-    let mut repos_q = conn.prepare("SELECT * FROM repository").unwrap();
-    let repo: Result<Repository> = repos_q.query_as_one([]);
+/// Print rows as a JSON array of objects, one row per line.
+fn format_json(rows: &[DynamicRow]) -> String {
+    let options = JsonOptions::default();
+    let mut out = String::from("[");
+    for (i, row) in rows.iter().enumerate() {
+        out.push_str(if i == 0 { "\n  " } else { ",\n  " });
+        out.push_str(&row.to_json(&options).to_string());
+    }
+    out.push_str(if rows.is_empty() { "]\n" } else { "\n]\n" });
+    out
+}
 
-    match repo {
-        Ok(repo) => {
-            println!("found! repo is {repo:#?}");
+/// Print every row as a plist like `(:id "1" :title "Physics")`.
+fn format_sexp(rows: &[DynamicRow]) -> String {
+    let mut out = String::new();
+    for row in rows {
+        writeln!(out, "{}", row.to_sexp()).unwrap();
+    }
+    out
+}
+
+fn run(args: Args) -> Result<String, Box<dyn Error>> {
+    let mode = if args.write {
+        OpenFlags::SQLITE_OPEN_READ_WRITE
+    } else {
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+    };
+    let conn = Connection::open_with_flags(&args.database, mode | OpenFlags::SQLITE_OPEN_URI)?;
+    #[cfg(feature = "functions")]
+    emacsql::register_functions(&conn)?;
+    let Some(src) = args.query else {
+        return Ok(list_tables(&conn)?);
+    };
+    let (columns, rows) = query(&conn, &src, &args.args)?;
+    Ok(match args.format {
+        Format::Table => format_table(&columns, &rows),
+        Format::Json => format_json(&rows),
+        Format::Sexp => format_sexp(&rows),
+    })
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(Some(args)) => args,
+        Ok(None) => {
+            println!("{USAGE}");
+            return ExitCode::SUCCESS;
+        }
+        Err(err) => {
+            eprintln!("emacsql: {err}\n\n{USAGE}");
+            return ExitCode::FAILURE;
         }
-        Err(Error::QueryReturnedNoRows) => {
-            println!("not found...:()");
+    };
+    match run(args) {
+        Ok(out) => {
+            print!("{out}");
+            ExitCode::SUCCESS
         }
         Err(err) => {
-            println!("{err:?}");
+            eprintln!("emacsql: {err}");
+            ExitCode::FAILURE
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id NOT NULL PRIMARY KEY, title, level INTEGER, olp);
+INSERT INTO nodes VALUES ('"1"', '"Physics"', 0, NULL);
+INSERT INTO nodes VALUES ('"2"', '"Momentum"', 1, '("Physics")');
+"#,
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = |argv: &[&str]| parse_args(argv.iter().map(|arg| arg.to_string()));
+        assert_eq!(
+            args(&["-f", "json", "db.sqlite", "[:select * :from nodes]", "1"]),
+            Ok(Some(Args {
+                database: "db.sqlite".into(),
+                query: Some("[:select * :from nodes]".into()),
+                args: vec!["1".into()],
+                format: Format::Json,
+                write: false,
+            }))
+        );
+        assert!(args(&["--write", "db.sqlite"]).unwrap().unwrap().write);
+        assert_eq!(args(&["db.sqlite", "--help"]), Ok(None));
+        assert!(args(&["-f", "xml", "db.sqlite"]).is_err());
+        assert!(args(&[]).is_err());
+    }
+
+    #[test]
+    fn test_list_tables() {
+        assert_eq!(
+            list_tables(&conn()).unwrap(),
+            "nodes (id, title, level, olp)\n"
+        );
+    }
+
+    #[test]
+    fn test_query_formats() {
+        let conn = conn();
+        let src = "[:select [id title olp] :from nodes :where (> level $s1)]";
+        let (columns, rows) = query(&conn, src, &["0".into()]).unwrap();
+        assert_eq!(
+            format_table(&columns, &rows),
+            "id | title    | olp\n-- | -------- | -----------\n2  | Momentum | (\"Physics\")\n"
+        );
+        assert_eq!(
+            format_json(&rows),
+            "[\n  {\"id\":\"2\",\"olp\":[\"Physics\"],\"title\":\"Momentum\"}\n]\n"
+        );
+        assert_eq!(
+            format_sexp(&rows),
+            "(:id \"2\" :title \"Momentum\" :olp (\"Physics\"))\n"
+        );
+
+        let (columns, rows) = query(
+            &conn,
+            "SELECT level FROM nodes WHERE id = ?",
+            &["\"1\"".into()],
+        )
+        .unwrap();
+        assert_eq!(columns.len(), 1);
+        assert_eq!(format_json(&rows), "[\n  {\"level\":0}\n]\n");
+
+        let (columns, rows) = query(&conn, "UPDATE nodes SET level = level", &[]).unwrap();
+        assert!(columns.is_empty());
+        assert_eq!(format_table(&columns, &rows), "");
+    }
+
+    #[test]
+    fn test_read_only() {
+        let path = std::env::temp_dir().join(format!("emacsql-{}.db", std::process::id()));
+        Connection::open(&path)
+            .unwrap()
+            .execute_batch("CREATE TABLE tags (node_id, tag)")
+            .unwrap();
+        let insert = |write| {
+            run(Args {
+                database: path.to_string_lossy().into_owned(),
+                query: Some("INSERT INTO tags VALUES ('\"1\"', '\"a\"')".into()),
+                args: Vec::new(),
+                format: Format::Table,
+                write,
+            })
+        };
+        let err = insert(false).unwrap_err();
+        assert!(err.to_string().contains("readonly"), "{err}");
+        assert!(insert(true).is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
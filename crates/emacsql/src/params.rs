@@ -0,0 +1,225 @@
+use rusqlite::types::{ToSql, ToSqlOutput, Value as SqlValue};
+
+use crate::lisp;
+use crate::value::Value;
+
+// EmacSQL escapes every parameter with `emacsql-escape-scalar`: `nil` becomes
+// NULL, numbers are inserted as numbers and everything else is `prin1`-ed into
+// a string.  So `"id"` is stored as the text "\"id\"", while `42` is an integer.
+
+/// Convert a rust value into the Emacs Lisp object which represents it.
+///
+/// This is the inverse of [`crate::value::FromLisp`].
+pub trait ToLisp {
+    fn to_lisp(&self) -> lisp::Value;
+}
+
+/// Convert a rust value into the value which EmacSQL stores in a database.
+///
+/// This is the inverse of [`crate::value::FromEmacsql`].  Values implement it
+/// for free when they implement [`ToLisp`].
+pub trait ToEmacsql {
+    fn to_emacsql(&self) -> Value;
+}
+
+impl<T: ToLisp + ?Sized> ToEmacsql for T {
+    fn to_emacsql(&self) -> Value {
+        Value::Lisp(self.to_lisp())
+    }
+}
+
+impl ToEmacsql for Value {
+    fn to_emacsql(&self) -> Value {
+        self.clone()
+    }
+}
+
+impl ToLisp for lisp::Value {
+    fn to_lisp(&self) -> lisp::Value {
+        self.clone()
+    }
+}
+
+impl ToLisp for str {
+    fn to_lisp(&self) -> lisp::Value {
+        lisp::Value::String(self.to_string())
+    }
+}
+
+impl ToLisp for String {
+    fn to_lisp(&self) -> lisp::Value {
+        self.as_str().to_lisp()
+    }
+}
+
+impl ToLisp for bool {
+    fn to_lisp(&self) -> lisp::Value {
+        if *self {
+            lisp::Value::Symbol("t".to_string())
+        } else {
+            lisp::Value::Nil
+        }
+    }
+}
+
+impl<T: ToLisp> ToLisp for Option<T> {
+    fn to_lisp(&self) -> lisp::Value {
+        match self {
+            Some(val) => val.to_lisp(),
+            None => lisp::Value::Nil,
+        }
+    }
+}
+
+impl<T: ToLisp> ToLisp for [T] {
+    fn to_lisp(&self) -> lisp::Value {
+        lisp::Value::list(self.iter().map(ToLisp::to_lisp).collect())
+    }
+}
+
+impl<T: ToLisp> ToLisp for Vec<T> {
+    fn to_lisp(&self) -> lisp::Value {
+        self.as_slice().to_lisp()
+    }
+}
+
+impl<T: ToLisp + ?Sized> ToLisp for &T {
+    fn to_lisp(&self) -> lisp::Value {
+        (*self).to_lisp()
+    }
+}
+
+macro_rules! some_integer_impls {
+    ($( $for:ident ),*) => {
+        $(
+            impl ToLisp for $for {
+                fn to_lisp(&self) -> lisp::Value {
+                    lisp::Value::Integer(*self as i64)
+                }
+            }
+        )*
+    };
+}
+
+some_integer_impls![i8, i16, i32, i64, u8, u16, u32, usize];
+
+macro_rules! some_real_impls {
+    ($( $for:ident ),*) => {
+        $(
+            impl ToLisp for $for {
+                fn to_lisp(&self) -> lisp::Value {
+                    lisp::Value::Real(*self as f64)
+                }
+            }
+        )*
+    };
+}
+
+some_real_impls![f32, f64];
+
+// from Value to sqlite::Value
+impl ToSql for Value {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        let val = match self {
+            Value::Null | Value::Lisp(lisp::Value::Nil) => SqlValue::Null,
+            Value::Integer(n) | Value::Lisp(lisp::Value::Integer(n)) => SqlValue::Integer(*n),
+            Value::Real(n) | Value::Lisp(lisp::Value::Real(n)) => SqlValue::Real(*n),
+            Value::Blob(bytes) => SqlValue::Blob(bytes.clone()),
+            Value::Lisp(sexp) => SqlValue::Text(sexp.to_lisp_string()),
+        };
+        Ok(ToSqlOutput::Owned(val))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rusqlite::types::ToSqlOutput;
+
+    fn sql_of<T: ToEmacsql + ?Sized>(val: &T) -> SqlValue {
+        match val.to_emacsql().to_sql().unwrap() {
+            ToSqlOutput::Owned(v) => v,
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn test_string_to_sql() {
+        assert_eq!(sql_of("momentum"), SqlValue::Text("\"momentum\"".into()));
+        assert_eq!(
+            sql_of(&"say \"hi\" \\o/".to_string()),
+            SqlValue::Text(r#""say \"hi\" \\o/""#.into())
+        );
+    }
+
+    #[test]
+    fn test_numbers_to_sql() {
+        assert_eq!(sql_of(&42usize), SqlValue::Integer(42));
+        assert_eq!(sql_of(&-1i32), SqlValue::Integer(-1));
+        assert_eq!(sql_of(&1.5f64), SqlValue::Real(1.5));
+    }
+
+    #[test]
+    fn test_nil_to_sql() {
+        assert_eq!(sql_of(&false), SqlValue::Null);
+        assert_eq!(sql_of(&None::<String>), SqlValue::Null);
+        assert_eq!(sql_of(&Vec::<String>::new()), SqlValue::Null);
+        assert_eq!(sql_of(&Value::Null), SqlValue::Null);
+    }
+
+    #[test]
+    fn test_lisp_to_sql() {
+        assert_eq!(sql_of(&true), SqlValue::Text("t".into()));
+        assert_eq!(
+            sql_of(&vec!["a", "b"]),
+            SqlValue::Text("(\"a\" \"b\")".into())
+        );
+        let cons = lisp::Value::Cons(
+            Box::new(lisp::Value::String("ID".into())),
+            Box::new(lisp::Value::Real(0.5)),
+        );
+        assert_eq!(sql_of(&cons), SqlValue::Text("(\"ID\" . 0.5)".into()));
+    }
+
+    #[test]
+    fn test_bind_and_read_back() {
+        use crate::query::QueryAs;
+        use crate::row::{FromRow, Row};
+        use crate::Result;
+
+        struct Node {
+            id: String,
+            level: i64,
+        }
+
+        impl FromRow for Node {
+            fn try_from_row(row: &Row) -> Result<Self> {
+                Ok(Self {
+                    id: row.get("id")?,
+                    level: row.get("level")?,
+                })
+            }
+        }
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE nodes (id, level)", []).unwrap();
+        conn.execute(
+            "INSERT INTO nodes VALUES (?1, ?2)",
+            ["a \"quoted\" id".to_emacsql(), 2.to_emacsql()],
+        )
+        .unwrap();
+
+        let stored: String = conn
+            .query_row("SELECT id FROM nodes", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(stored, r#""a \"quoted\" id""#);
+
+        let node: Node = conn
+            .prepare("SELECT id, level FROM nodes WHERE id = ?1")
+            .unwrap()
+            .query_as_one(["a \"quoted\" id".to_emacsql()])
+            .unwrap();
+        assert_eq!(node.id, "a \"quoted\" id");
+        assert_eq!(node.level, 2);
+    }
+}
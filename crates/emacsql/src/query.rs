@@ -1,29 +1,315 @@
+use std::marker::PhantomData;
+
 use rusqlite::{self, Params};
 
-use crate::error::Error;
+use crate::error::{DecodeError, Error, FromEmacsqlError};
 use crate::prelude::Result;
 use crate::row::{FromRow, Row};
+use crate::schema::ColumnTypes;
+use crate::value::{FromEmacsql, InvalidText};
 
 pub trait QueryAs {
     fn query_as_one<P: Params, T: FromRow>(&mut self, params: P) -> Result<T>;
     fn query_as<P: Params, T: FromRow>(&mut self, params: P) -> Result<Vec<T>>;
+
+    /// Like [`QueryAs::query_as`], but read rows lazily one by one, so a huge
+    /// result set isn't stored in memory.
+    fn query_as_iter<P: Params, T: FromRow>(&mut self, params: P) -> Result<RowsAs<'_, T>>;
+
+    /// Return the first column of the first row, like the count of
+    /// `SELECT count(*) FROM nodes`.
+    fn query_scalar<P: Params, T: FromEmacsql>(&mut self, params: P) -> Result<T>;
 }
 
 impl<'a> QueryAs for rusqlite::Statement<'a> {
     fn query_as_one<P: Params, T: FromRow>(&mut self, params: P) -> Result<T> {
-        self.query(params)?
-            .next()?
-            .ok_or(Error::QueryReturnedNoRows)
-            .map(Row::from) // converts rusqlite Row to emacsql Row
-            .and_then(|r| T::try_from_row(&r))
+        self.query_as_iter(params)?
+            .next()
+            .unwrap_or(Err(Error::QueryReturnedNoRows))
     }
 
     fn query_as<P: Params, T: FromRow>(&mut self, params: P) -> Result<Vec<T>> {
-        let mut rows = self.query(params)?;
-        let mut objs = Vec::new();
-        while let Some(row) = rows.next()? {
-            objs.push(T::try_from_row(&row.into())?);
+        self.query_as_iter(params)?.collect()
+    }
+
+    fn query_as_iter<P: Params, T: FromRow>(&mut self, params: P) -> Result<RowsAs<'_, T>> {
+        Ok(RowsAs {
+            rows: self.query(params)?,
+            invalid_text: InvalidText::default(),
+            column_types: None,
+            marker: PhantomData,
+        })
+    }
+
+    fn query_scalar<P: Params, T: FromEmacsql>(&mut self, params: P) -> Result<T> {
+        self.query_as_one(params).map(|(value,)| value)
+    }
+}
+
+/// Queries whose columns are decoded according to their declared types, so
+/// plain text columns like `nodes.scheduled` of org-roam aren't read as lisp.
+///
+/// The types are only known for columns read straight from one table:
+///
+/// - The table is found in the SQL of the query, so the columns of a join,
+///   a compound select or a select from a subquery fall back to the types
+///   SQLite reports, and a `text` column is read as an `object`.
+/// - A column renamed with `AS` is typed by guessing its column from the
+///   type SQLite reports, see [`ColumnTypes::of_statement`].
+/// - A [`rusqlite::Statement`] doesn't know its connection, which the types
+///   are read from, so [`QueryAs`] on a statement does no typing.  Pass
+///   [`ColumnTypes`] to [`RowsAs::column_types`] instead.
+pub trait QueryTyped {
+    fn query_typed_one<P: Params, T: FromRow>(&self, sql: &str, params: P) -> Result<T>;
+    fn query_typed<P: Params, T: FromRow>(&self, sql: &str, params: P) -> Result<Vec<T>>;
+}
+
+impl QueryTyped for rusqlite::Connection {
+    fn query_typed_one<P: Params, T: FromRow>(&self, sql: &str, params: P) -> Result<T> {
+        let mut stmt = self.prepare(sql)?;
+        let types = ColumnTypes::of_statement(self, &stmt)?;
+        let first = stmt.query_as_iter(params)?.column_types(types).next();
+        first.unwrap_or(Err(Error::QueryReturnedNoRows))
+    }
+
+    fn query_typed<P: Params, T: FromRow>(&self, sql: &str, params: P) -> Result<Vec<T>> {
+        let mut stmt = self.prepare(sql)?;
+        let types = ColumnTypes::of_statement(self, &stmt)?;
+        let rows = stmt.query_as_iter(params)?.column_types(types).collect();
+        rows
+    }
+}
+
+/// An iterator over the rows of a query converted with [`FromRow`].
+pub struct RowsAs<'stmt, T> {
+    rows: rusqlite::Rows<'stmt>,
+    invalid_text: InvalidText,
+    column_types: Option<ColumnTypes>,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RowsAs<'_, T> {
+    /// Handle text columns which aren't UTF-8 according to `policy`.
+    pub fn invalid_text(mut self, policy: InvalidText) -> Self {
+        self.invalid_text = policy;
+        self
+    }
+
+    /// Decode columns according to their declared types, see
+    /// [`ColumnTypes::of_statement`].
+    pub fn column_types(mut self, types: ColumnTypes) -> Self {
+        self.column_types = Some(types);
+        self
+    }
+}
+
+impl<T: FromRow> Iterator for RowsAs<'_, T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let row = match self.rows.next() {
+                Ok(Some(row)) => row,
+                Ok(None) => return None,
+                Err(err) => return Some(Err(err)),
+            };
+            let mut row = Row::with_invalid_text(row, self.invalid_text);
+            if let Some(types) = &self.column_types {
+                row = row.column_types(types);
+            }
+            match T::try_from_row(&row) {
+                Err(err) if self.invalid_text == InvalidText::SkipRow && is_invalid_utf8(&err) => {
+                    continue
+                }
+                res => return Some(res),
+            }
         }
-        Ok(objs)
+    }
+}
+
+/// Return true, if a row wasn't converted, because its text isn't UTF-8.
+fn is_invalid_utf8(err: &Error) -> bool {
+    let Error::FromSqlConversionFailure(_, _, err) = err else {
+        return false;
+    };
+    matches!(
+        err.downcast_ref::<DecodeError>(),
+        Some(DecodeError { source: FromEmacsqlError::Other(source), .. })
+            if source.is::<std::str::Utf8Error>()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::row::OwnedRow;
+
+    struct Link {
+        source: String,
+        dest: String,
+    }
+
+    impl FromRow for Link {
+        fn try_from_row(row: &Row) -> Result<Self> {
+            Ok(Self {
+                source: row.get("source")?,
+                dest: row.get("dest")?,
+            })
+        }
+    }
+
+    #[test]
+    fn test_query_as_iter() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE links (source, dest);
+INSERT INTO links VALUES ('"1"', '"2"'), ('"2"', '"3"'), ('"3"', 'broken');
+"#,
+        )
+        .unwrap();
+        let mut stmt = conn.prepare("SELECT source, dest FROM links").unwrap();
+        let mut links = stmt.query_as_iter::<_, Link>([]).unwrap();
+
+        let first = links.next().unwrap().unwrap();
+        assert_eq!((first.source, first.dest), ("1".into(), "2".into()));
+        assert!(links.next().unwrap().is_ok());
+        assert!(links.next().unwrap().is_err());
+        assert!(links.next().is_none());
+
+        let none = conn
+            .prepare("SELECT source, dest FROM links WHERE 0")
+            .unwrap()
+            .query_as_one::<_, Link>([]);
+        assert!(matches!(none, Err(Error::QueryReturnedNoRows)));
+    }
+
+    #[test]
+    fn test_invalid_text() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE links (source, dest);
+INSERT INTO links VALUES ('"1"', '"2"'), ('"2"', CAST(X'22FF6122' AS TEXT));
+"#,
+        )
+        .unwrap();
+        let mut stmt = conn.prepare("SELECT source, dest FROM links").unwrap();
+        let mut dests = |policy| {
+            stmt.query_as_iter::<_, Link>([])
+                .unwrap()
+                .invalid_text(policy)
+                .map(|link| link.map(|link| link.dest))
+                .collect::<Result<Vec<_>>>()
+        };
+
+        assert_eq!(dests(InvalidText::Lossy).unwrap(), ["2", "\u{FFFD}a"]);
+        assert_eq!(dests(InvalidText::SkipRow).unwrap(), ["2"]);
+        let err = dests(InvalidText::Error).unwrap_err();
+        assert!(is_invalid_utf8(&err));
+        // a raw byte can't be a part of `String`
+        let err = dests(InvalidText::Raw).unwrap_err();
+        assert!(!is_invalid_utf8(&err));
+    }
+
+    #[test]
+    fn test_tuples_and_scalars() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE tags (node_id, tag);
+INSERT INTO tags VALUES ('"1"', '"physics"'), ('"2"', '"physics"'), ('"2"', '"math"');
+"#,
+        )
+        .unwrap();
+        let counts: Vec<(String, i64)> = conn
+            .prepare("SELECT tag, count(*) FROM tags GROUP BY tag ORDER BY tag")
+            .unwrap()
+            .query_as([])
+            .unwrap();
+        assert_eq!(counts, [("math".into(), 1), ("physics".into(), 2)]);
+
+        let mut stmt = conn
+            .prepare("SELECT count(*) FROM tags WHERE tag = ?")
+            .unwrap();
+        assert_eq!(stmt.query_scalar::<_, i64>(["\"math\""]).unwrap(), 1);
+        let tags: Vec<String> = conn
+            .prepare("SELECT DISTINCT tag FROM tags ORDER BY tag")
+            .unwrap()
+            .query_as([])
+            .unwrap();
+        assert_eq!(tags, ["math", "physics"]);
+    }
+
+    #[test]
+    fn test_owned_row() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id, title, level);
+INSERT INTO nodes VALUES ('"1"', '"Physics"', 0);
+"#,
+        )
+        .unwrap();
+        let row: OwnedRow = conn
+            .prepare("SELECT * FROM nodes")
+            .unwrap()
+            .query_as_one([])
+            .unwrap();
+        let row = std::thread::spawn(move || row).join().unwrap();
+        assert_eq!(row.len(), 3);
+        assert_eq!(row.get::<_, String>("TITLE").unwrap(), "Physics");
+        assert_eq!(row.get::<_, i64>(2).unwrap(), 0);
+        assert!(matches!(
+            row.get::<_, i64>("todo"),
+            Err(Error::InvalidColumnName(_))
+        ));
+        let err = row.get::<_, i64>("title").unwrap_err();
+        assert!(err.to_string().contains("Physics"), "{err}");
+    }
+
+    #[test]
+    fn test_column_types() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::schema::Schema::org_roam().create(&conn).unwrap();
+        conn.execute_batch(
+            r#"
+INSERT INTO nodes (id, file, level, pos, scheduled, deadline, title)
+VALUES ('"1"', '"a.org"', 0, 1, '"2023-04-05T10:00:00+0300"', '<2023-04-06 Thu>', '"(a)"');
+"#,
+        )
+        .unwrap();
+        let mut stmt = conn
+            .prepare("SELECT scheduled, deadline, title FROM nodes")
+            .unwrap();
+        let types = ColumnTypes::of_statement(&conn, &stmt).unwrap();
+        let rows: Vec<(String, String, String)> = stmt
+            .query_as_iter([])
+            .unwrap()
+            .column_types(types)
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [(
+                "2023-04-05T10:00:00+0300".into(),
+                "<2023-04-06 Thu>".into(),
+                "(a)".into()
+            )]
+        );
+        // without the types, the deadline is read as a symbol
+        let err = stmt
+            .query_as_one::<_, (String, String, String)>([])
+            .unwrap_err();
+        assert!(err.to_string().contains("deadline"), "{err}");
+
+        let deadline: String = conn
+            .query_typed_one("SELECT deadline FROM nodes", [])
+            .map(|(deadline,)| deadline)
+            .unwrap();
+        assert_eq!(deadline, "<2023-04-06 Thu>");
+        // the columns of a compound select have no types
+        let union = "SELECT title FROM nodes UNION SELECT deadline FROM nodes";
+        assert!(conn.query_typed::<_, (String,)>(union, []).is_err());
     }
 }
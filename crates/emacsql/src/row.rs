@@ -1,42 +1,386 @@
-use rusqlite;
+use rusqlite::{
+    self,
+    types::{Type, ValueRef},
+};
 
-use crate::error::Error;
+use crate::error::{DecodeError, Error, FromEmacsqlError};
+use crate::lisp;
 use crate::prelude::*;
-use crate::value::FromEmacsql;
+use crate::schema::{ColumnType, ColumnTypes};
+use crate::value::{plain_text, FromEmacsql, FromLisp, InvalidText, Value};
 
 pub struct Row<'a> {
     row: &'a rusqlite::Row<'a>,
+    invalid_text: InvalidText,
+    types: Option<&'a ColumnTypes>,
 }
 
 impl<'a> Row<'a> {
-    pub fn get<I: RowIndex + Clone, T: FromEmacsql>(&self, idx: I) -> Result<T> {
-        let eql = self.row.get(idx.clone())?;
-        T::from_emacsql(eql).map_err(|_| idx.as_invalid())
+    /// Return a row which handles text that isn't UTF-8 according to `policy`.
+    pub fn with_invalid_text(row: &'a rusqlite::Row<'a>, policy: InvalidText) -> Self {
+        Self {
+            row,
+            invalid_text: policy,
+            types: None,
+        }
+    }
+
+    /// Decode columns according to their declared types, so plain text
+    /// columns aren't read as lisp.
+    pub fn column_types(mut self, types: &'a ColumnTypes) -> Self {
+        self.types = Some(types);
+        self
+    }
+
+    /// Return the declared type of a column, if it's known.
+    pub fn column_type(&self, idx: usize) -> Option<&ColumnType> {
+        self.types?.get(idx)
+    }
+
+    /// Read a column, a failure is [`DecodeError`] wrapped into
+    /// [`Error::FromSqlConversionFailure`].
+    pub fn get<I: RowIndex, T: FromEmacsql>(&self, idx: I) -> Result<T> {
+        let idx = idx.idx(self.row.as_ref())?;
+        let raw = self.row.get_ref(idx)?;
+        Value::from_sql_typed(raw, self.invalid_text, self.column_type(idx))
+            .and_then(T::from_emacsql)
+            .map_err(|source| self.decode_error(idx, raw, source))
+    }
+
+    /// Read a column as a lisp value which borrows its strings and symbols
+    /// from the row instead of copying them.
+    pub fn get_borrowed<I: RowIndex>(&self, idx: I) -> Result<lisp::ValueRef<'a>> {
+        let idx = idx.idx(self.row.as_ref())?;
+        let raw = self.row.get_ref(idx)?;
+        let value = match raw {
+            ValueRef::Null => Ok(lisp::ValueRef::Nil),
+            ValueRef::Integer(n) => Ok(lisp::ValueRef::Integer(n)),
+            ValueRef::Real(n) => Ok(lisp::ValueRef::Real(n)),
+            ValueRef::Text(text) => std::str::from_utf8(text)
+                .map_err(|err| FromEmacsqlError::Other(Box::new(err)))
+                .and_then(|text| match self.column_type(idx) {
+                    Some(ty) if ty.is_text() => Ok(lisp::ValueRef::String(plain_text(text))),
+                    _ => Ok(lisp::parse_borrowed(text)?),
+                }),
+            ValueRef::Blob(bytes) => Ok(lisp::ValueRef::Unibyte(bytes.to_vec())),
+        };
+        value.map_err(|source| self.decode_error(idx, raw, source))
+    }
+
+    /// Decode every column, so the row can outlive its statement.
+    pub fn to_owned_row(&self) -> Result<OwnedRow> {
+        let stmt = self.row.as_ref();
+        let columns = (0..stmt.column_count())
+            .map(|idx| {
+                let raw = self.row.get_ref(idx)?;
+                let value = Value::from_sql_typed(raw, self.invalid_text, self.column_type(idx))
+                    .map_err(|source| self.decode_error(idx, raw, source))?;
+                Ok((stmt.column_name(idx)?.to_string(), value))
+            })
+            .collect::<Result<_>>()?;
+        Ok(OwnedRow { columns })
+    }
+
+    /// Return true, if the query selects a column with a given name.
+    pub fn has_column(&self, name: &str) -> bool {
+        self.row.as_ref().column_index(name).is_ok()
+    }
+
+    fn decode_error(&self, idx: usize, raw: ValueRef, source: FromEmacsqlError) -> Error {
+        let stmt = self.row.as_ref();
+        let err = DecodeError {
+            table: stmt.expanded_sql().as_deref().and_then(single_table),
+            column: stmt
+                .column_name(idx)
+                .map_or_else(|_| idx.to_string(), str::to_string),
+            text: match raw {
+                ValueRef::Text(text) => Some(String::from_utf8_lossy(text).into_owned()),
+                _ => None,
+            },
+            source,
+        };
+        Error::FromSqlConversionFailure(idx, raw.data_type(), Box::new(err))
     }
 }
 
+/// Return the name of the table which a query reads, if it reads only one
+/// table: `SELECT id FROM nodes WHERE ...` reads `nodes`.
+///
+/// The rows of a compound select like `... UNION SELECT ...` come from
+/// several selects, so it has no single table.
+pub(crate) fn single_table(sql: &str) -> Option<String> {
+    if is_compound(sql) {
+        return None;
+    }
+    let mut words = sql.split_whitespace();
+    words.find(|w| w.eq_ignore_ascii_case("from"))?;
+    let table = words.next()?.trim_end_matches(';');
+    let reads_more = words
+        .take_while(|w| {
+            !["where", "group", "order", "limit"]
+                .iter()
+                .any(|kw| w.eq_ignore_ascii_case(kw))
+        })
+        .any(|w| w.contains(',') || w.eq_ignore_ascii_case("join"));
+    let is_name = table
+        .chars()
+        .all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '"' | '`'));
+    if reads_more || !is_name || table.is_empty() {
+        return None;
+    }
+    Some(table.trim_matches(|c| c == '"' || c == '`').to_string())
+}
+
+/// Return true for a compound select like `... UNION SELECT ...`.
+pub(crate) fn is_compound(sql: &str) -> bool {
+    sql.split_whitespace().any(|w| {
+        ["union", "intersect", "except"]
+            .iter()
+            .any(|kw| w.eq_ignore_ascii_case(kw))
+    })
+}
+
 pub trait RowIndex: rusqlite::RowIndex {
-    fn as_invalid(&self) -> Error;
+    /// Return the position of this column among the columns with `names`.
+    fn position<'a>(&self, names: impl ExactSizeIterator<Item = &'a str>) -> Result<usize>;
 }
 
 impl RowIndex for &'_ str {
-    fn as_invalid(&self) -> Error {
-        Error::InvalidColumnName(self.to_string())
+    fn position<'a>(&self, mut names: impl ExactSizeIterator<Item = &'a str>) -> Result<usize> {
+        // SQLite compares column names ignoring ASCII case
+        names
+            .position(|name| name.eq_ignore_ascii_case(self))
+            .ok_or_else(|| Error::InvalidColumnName(self.to_string()))
     }
 }
 
 impl RowIndex for usize {
-    fn as_invalid(&self) -> Error {
-        Error::InvalidColumnIndex(*self)
+    fn position<'a>(&self, names: impl ExactSizeIterator<Item = &'a str>) -> Result<usize> {
+        if *self < names.len() {
+            Ok(*self)
+        } else {
+            Err(Error::InvalidColumnIndex(*self))
+        }
+    }
+}
+
+/// A row with decoded columns, which doesn't borrow its statement, so it can
+/// be kept or sent to another thread.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct OwnedRow {
+    columns: Vec<(String, Value)>,
+}
+
+impl OwnedRow {
+    /// Convert a column like [`Row::get`] does.
+    pub fn get<I: RowIndex, T: FromEmacsql>(&self, idx: I) -> Result<T> {
+        let idx = idx.position(self.columns.iter().map(|(name, _)| name.as_str()))?;
+        let (column, value) = &self.columns[idx];
+        T::from_emacsql(value.clone()).map_err(|source| {
+            let (data_type, text) = match value {
+                Value::Lisp(sexp) => (Type::Text, Some(sexp.to_lisp_string())),
+                Value::Integer(_) => (Type::Integer, None),
+                Value::Real(_) => (Type::Real, None),
+                Value::Null => (Type::Null, None),
+                Value::Blob(_) => (Type::Blob, None),
+            };
+            let err = DecodeError {
+                table: None,
+                column: column.clone(),
+                text,
+                source,
+            };
+            Error::FromSqlConversionFailure(idx, data_type, Box::new(err))
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.columns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+    }
+
+    /// Return the names of columns and their values.
+    pub fn columns(&self) -> &[(String, Value)] {
+        &self.columns
+    }
+
+    pub fn into_columns(self) -> Vec<(String, Value)> {
+        self.columns
+    }
+}
+
+impl From<Vec<(String, Value)>> for OwnedRow {
+    fn from(columns: Vec<(String, Value)>) -> Self {
+        Self { columns }
     }
 }
 
 impl<'a> From<&'a rusqlite::Row<'a>> for Row<'a> {
     fn from(row: &'a rusqlite::Row) -> Self {
-        Self { row }
+        Self::with_invalid_text(row, InvalidText::default())
     }
 }
 
 pub trait FromRow: Sized {
     fn try_from_row(row: &Row) -> Result<Self>;
 }
+
+impl FromRow for OwnedRow {
+    fn try_from_row(row: &Row) -> Result<Self> {
+        row.to_owned_row()
+    }
+}
+
+/// A tuple is read from the first columns of a row, one column per item.
+macro_rules! some_tuple_impls {
+    ($( ($( $idx:tt: $name:ident ),+) ),*) => {
+        $(
+            impl<$( $name: FromEmacsql ),+> FromRow for ($( $name, )+) {
+                fn try_from_row(row: &Row) -> Result<Self> {
+                    Ok(($( row.get::<usize, $name>($idx)?, )+))
+                }
+            }
+        )*
+    };
+}
+
+some_tuple_impls![
+    (0: A),
+    (0: A, 1: B),
+    (0: A, 1: B, 2: C),
+    (0: A, 1: B, 2: C, 3: D),
+    (0: A, 1: B, 2: C, 3: D, 4: E),
+    (0: A, 1: B, 2: C, 3: D, 4: E, 5: F)
+];
+
+/// A single value is read from the first column of a row.
+///
+/// Tuples are values too, so there's no impl for every [`FromEmacsql`], only
+/// for the common ones.
+macro_rules! some_scalar_impls {
+    ($( $for:ty ),*) => {
+        $(
+            impl FromRow for $for {
+                fn try_from_row(row: &Row) -> Result<Self> {
+                    row.get(0)
+                }
+            }
+        )*
+    };
+}
+
+some_scalar_impls![
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    f32,
+    f64,
+    bool,
+    char,
+    String,
+    lisp::Value
+];
+
+impl<T: FromLisp> FromRow for Option<T> {
+    fn try_from_row(row: &Row) -> Result<Self> {
+        row.get(0)
+    }
+}
+
+impl<T: FromLisp> FromRow for Vec<T> {
+    fn try_from_row(row: &Row) -> Result<Self> {
+        row.get(0)
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use super::*;
+    use crate::QueryAs;
+
+    #[derive(crate::FromRow, Debug, PartialEq)]
+    struct Node {
+        id: String,
+        #[emacsql(rename = "file")]
+        filename: String,
+        title: Option<String>,
+        #[emacsql(default)]
+        level: i64,
+        #[emacsql(skip)]
+        tags: Vec<String>,
+    }
+
+    #[derive(crate::FromRow, Debug, PartialEq)]
+    struct Link(String, String);
+
+    fn conn() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (id, file, title, level);
+INSERT INTO nodes VALUES ('"1"', '"org-roam/momentum.org"', NULL, 2);
+CREATE TABLE links (source, dest);
+INSERT INTO links VALUES ('"1"', '"2"');
+"#,
+        )
+        .unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_derive_from_row() {
+        let node: Node = conn()
+            .prepare("SELECT * FROM nodes")
+            .unwrap()
+            .query_as_one([])
+            .unwrap();
+        assert_eq!(
+            node,
+            Node {
+                id: "1".into(),
+                filename: "org-roam/momentum.org".into(),
+                title: None,
+                level: 2,
+                tags: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn test_derive_optional_columns() {
+        let node: Node = conn()
+            .prepare("SELECT id, file FROM nodes")
+            .unwrap()
+            .query_as_one([])
+            .unwrap();
+        assert_eq!(node.level, 0);
+        assert_eq!(node.title, None);
+
+        let missing: Result<Node> = conn()
+            .prepare("SELECT id, title FROM nodes")
+            .unwrap()
+            .query_as_one([]);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn test_derive_tuple_struct() {
+        let link: Link = conn()
+            .prepare("SELECT source, dest FROM links")
+            .unwrap()
+            .query_as_one([])
+            .unwrap();
+        assert_eq!(link, Link("1".into(), "2".into()));
+    }
+}
@@ -0,0 +1,886 @@
+//! EmacSQL table schemata.
+//!
+//! EmacSQL describes a table with a vector of columns, optionally followed by
+//! table constraints:
+//!
+//! ```elisp
+//! ([(id :not-null :primary-key) (file :not-null) title]
+//!  (:foreign-key [file] :references files [file] :on-delete :cascade))
+//! ```
+//!
+//! A column is a symbol or a list of its name, an optional type and
+//! constraints.  This module parses such definitions and emits the same
+//! `CREATE TABLE` and `CREATE INDEX` statements as EmacSQL does.
+
+use std::fmt::{self, Display};
+
+use rusqlite::{Connection, OptionalExtension, Statement};
+
+use crate::compiler::{compile_expr, identifier};
+use crate::lisp;
+use crate::row::{is_compound, single_table};
+
+/// The tables of an org-roam database, `org-roam-db--table-schemata`.
+pub const ORG_ROAM_SCHEMATA: &str = r#"
+((files
+  [(file :unique :primary-key)
+   title
+   (hash :not-null)
+   (atime :not-null)
+   (mtime :not-null)])
+
+ (nodes
+  ([(id :not-null :primary-key)
+    (file :not-null)
+    (level :not-null)
+    (pos :not-null)
+    todo
+    priority
+    (scheduled text)
+    (deadline text)
+    title
+    properties
+    olp]
+   (:foreign-key [file] :references files [file] :on-delete :cascade)))
+
+ (aliases
+  ([(node-id :not-null)
+    alias]
+   (:foreign-key [node-id] :references nodes [id] :on-delete :cascade)))
+
+ (citations
+  ([(node-id :not-null)
+    (cite-key :not-null)
+    (pos :not-null)
+    properties]
+   (:foreign-key [node-id] :references nodes [id] :on-delete :cascade)))
+
+ (refs
+  ([(node-id :not-null)
+    (ref :not-null)
+    (type :not-null)]
+   (:foreign-key [node-id] :references nodes [id] :on-delete :cascade)))
+
+ (tags
+  ([(node-id :not-null)
+    tag]
+   (:foreign-key [node-id] :references nodes [id] :on-delete :cascade)))
+
+ (links
+  ([(pos :not-null)
+    (source :not-null)
+    (dest :not-null)
+    (type :not-null)
+    (properties :not-null)]
+   (:foreign-key [source] :references nodes [id] :on-delete :cascade))))
+"#;
+
+/// The indices of an org-roam database, `org-roam-db--table-indices`.
+pub const ORG_ROAM_INDICES: &str = r#"
+((alias-node-id aliases [node-id])
+ (refs-node-id refs [node-id])
+ (tags-node-id tags [node-id]))
+"#;
+
+/// `org-roam-db-version`, org-roam rebuilds a database when its
+/// `PRAGMA user_version` is different.
+pub const ORG_ROAM_DB_VERSION: i64 = 18;
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Read(lisp::Error),
+    /// a part of a schema which can't be understood
+    InvalidSchema(lisp::Value),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Read(err) => write!(f, "can't read a schema: {err}"),
+            Self::InvalidSchema(val) => write!(f, "invalid schema {val}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<lisp::Error> for Error {
+    fn from(err: lisp::Error) -> Self {
+        Self::Read(err)
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// The tables and indices of a database.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Schema {
+    pub tables: Vec<Table>,
+    pub indices: Vec<Index>,
+    /// `PRAGMA user_version` of a created database, which a package like
+    /// org-roam checks
+    pub user_version: Option<i64>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Table {
+    pub name: String,
+    pub columns: Vec<Column>,
+    pub constraints: Vec<TableConstraint>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Column {
+    pub name: String,
+    pub ty: Option<ColumnType>,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+/// A type of a column, EmacSQL maps `integer`, `float` and `object` to SQL
+/// types and leaves other names as they are.
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColumnType {
+    Integer,
+    Float,
+    Object,
+    Other(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ColumnConstraint {
+    NotNull,
+    PrimaryKey,
+    Unique,
+    Autoincrement,
+    Default(lisp::Value),
+    Check(lisp::Value),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<String>),
+    Unique(Vec<String>),
+    Check(lisp::Value),
+    ForeignKey {
+        columns: Vec<String>,
+        table: String,
+        references: Vec<String>,
+        on_delete: Option<ForeignKeyAction>,
+        on_update: Option<ForeignKeyAction>,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ForeignKeyAction {
+    SetNull,
+    SetDefault,
+    Cascade,
+    Restrict,
+    NoAction,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Index {
+    pub name: String,
+    pub table: String,
+    pub columns: Vec<String>,
+}
+
+impl Schema {
+    /// Parse schemata and indices in the format of
+    /// `org-roam-db--table-schemata` and `org-roam-db--table-indices`: lists
+    /// of `(table schema)` and `(index table [columns])`.
+    pub fn parse(schemata: &str, indices: &str) -> Result<Self> {
+        let tables = entries(&schemata.parse()?)?
+            .iter()
+            .map(|entry| match entry.as_slice() {
+                [lisp::Value::Symbol(name), schema] => Table::parse(name, schema),
+                _ => Err(invalid(&lisp::Value::list(entry.clone()))),
+            })
+            .collect::<Result<_>>()?;
+        let indices = entries(&indices.parse()?)?
+            .iter()
+            .map(|entry| match entry.as_slice() {
+                [lisp::Value::Symbol(name), lisp::Value::Symbol(table), columns] => Ok(Index {
+                    name: name.clone(),
+                    table: table.clone(),
+                    columns: names(columns)?,
+                }),
+                _ => Err(invalid(&lisp::Value::list(entry.clone()))),
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            tables,
+            indices,
+            user_version: None,
+        })
+    }
+
+    /// The schema of an org-roam database.
+    pub fn org_roam() -> Self {
+        Self {
+            user_version: Some(ORG_ROAM_DB_VERSION),
+            ..Self::parse(ORG_ROAM_SCHEMATA, ORG_ROAM_INDICES).expect("org-roam schema is valid")
+        }
+    }
+
+    /// Return the statements which create every table and index.
+    pub fn to_sql(&self) -> Vec<String> {
+        let tables = self.tables.iter().map(Table::to_sql);
+        tables
+            .chain(self.indices.iter().map(Index::to_sql))
+            .collect()
+    }
+
+    /// Create every table and index in a database and set its
+    /// `user_version`.
+    pub fn create(&self, conn: &Connection) -> crate::Result<()> {
+        for sql in self.to_sql() {
+            conn.execute(&sql, [])?;
+        }
+        if let Some(version) = self.user_version {
+            conn.pragma_update(None, "user_version", version)?;
+        }
+        Ok(())
+    }
+}
+
+impl Table {
+    /// Parse the schema of a table, a vector of columns or a list of it and
+    /// table constraints.
+    pub fn parse(name: &str, schema: &lisp::Value) -> Result<Self> {
+        let (columns, constraints) = match schema {
+            lisp::Value::Vector(columns) => (columns.as_slice(), &[][..]),
+            lisp::Value::List(items) => match items.as_slice() {
+                [lisp::Value::Vector(columns), constraints @ ..] => {
+                    (columns.as_slice(), constraints)
+                }
+                _ => return Err(invalid(schema)),
+            },
+            _ => return Err(invalid(schema)),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            columns: columns.iter().map(Column::parse).collect::<Result<_>>()?,
+            constraints: constraints
+                .iter()
+                .map(TableConstraint::parse)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Return the `CREATE TABLE` statement of this table.
+    pub fn to_sql(&self) -> String {
+        let defs = self.columns.iter().map(Column::to_sql);
+        let defs: Vec<_> = defs
+            .chain(self.constraints.iter().map(TableConstraint::to_sql))
+            .collect();
+        format!(
+            "CREATE TABLE {} ({})",
+            identifier(&self.name),
+            defs.join(", ")
+        )
+    }
+}
+
+impl Table {
+    /// Read a table of a database with `PRAGMA table_info`, `None` if there's
+    /// no such table.
+    ///
+    /// Only the names, the types, `NOT NULL` and primary keys of columns are
+    /// read, other constraints are not.
+    pub fn introspect(conn: &Connection, name: &str) -> crate::Result<Option<Self>> {
+        let create: Option<String> = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let mut stmt =
+            conn.prepare("SELECT name, type, \"notnull\", pk FROM pragma_table_info(?1)")?;
+        let mut columns = Vec::new();
+        // the names of the primary key columns by their positions in the key
+        let mut primary_key = Vec::new();
+        let mut rows = stmt.query([name])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let mut constraints = Vec::new();
+            if row.get(2)? {
+                constraints.push(ColumnConstraint::NotNull);
+            }
+            let pk: usize = row.get(3)?;
+            if pk > 0 {
+                primary_key.push((pk, name.clone()));
+            }
+            let mut decl: String = row.get(1)?;
+            if decl == "TEXT" {
+                if let Some(spelled) = create.as_deref().and_then(|sql| spelled_type(sql, &name)) {
+                    decl = spelled.to_string();
+                }
+            }
+            columns.push(Column {
+                ty: ColumnType::from_sql(&decl),
+                name,
+                constraints,
+            });
+        }
+        if columns.is_empty() {
+            return Ok(None);
+        }
+
+        let mut constraints = Vec::new();
+        primary_key.sort();
+        match primary_key.as_slice() {
+            [] => {}
+            [(_, key)] => {
+                let column = columns.iter_mut().find(|col| &col.name == key).unwrap();
+                column.constraints.push(ColumnConstraint::PrimaryKey);
+            }
+            keys => constraints.push(TableConstraint::PrimaryKey(
+                keys.iter().map(|(_, key)| key.clone()).collect(),
+            )),
+        }
+        Ok(Some(Self {
+            name: name.to_string(),
+            columns,
+            constraints,
+        }))
+    }
+}
+
+impl Column {
+    /// Parse a column like `title` or `(id text :not-null)`.
+    pub fn parse(def: &lisp::Value) -> Result<Self> {
+        let items = match def {
+            lisp::Value::Symbol(_) => std::slice::from_ref(def),
+            lisp::Value::List(items) => items.as_slice(),
+            _ => return Err(invalid(def)),
+        };
+        let (name, mut rest) = match items {
+            [lisp::Value::Symbol(name), rest @ ..] if !name.starts_with(':') => (name, rest),
+            _ => return Err(invalid(def)),
+        };
+        let ty = match rest {
+            [lisp::Value::Symbol(ty), tail @ ..] if !ty.starts_with(':') => {
+                rest = tail;
+                Some(ColumnType::from_name(ty))
+            }
+            _ => None,
+        };
+
+        let mut constraints = Vec::new();
+        while let [keyword, tail @ ..] = rest {
+            let (constraint, tail) = match (keyword_of(keyword), tail) {
+                (Some("not-null"), tail) => (ColumnConstraint::NotNull, tail),
+                (Some("primary-key"), tail) => (ColumnConstraint::PrimaryKey, tail),
+                (Some("unique"), tail) => (ColumnConstraint::Unique, tail),
+                (Some("autoincrement"), tail) => (ColumnConstraint::Autoincrement, tail),
+                (Some("default"), [val, tail @ ..]) => {
+                    (ColumnConstraint::Default(val.clone()), tail)
+                }
+                (Some("check"), [val, tail @ ..]) => (ColumnConstraint::Check(val.clone()), tail),
+                _ => return Err(invalid(def)),
+            };
+            constraints.push(constraint);
+            rest = tail;
+        }
+        Ok(Self {
+            name: name.clone(),
+            ty,
+            constraints,
+        })
+    }
+
+    /// Return the definition of this column in `CREATE TABLE`.
+    ///
+    /// Like EmacSQL, the name is always followed by a space, even if the
+    /// column has neither a type nor constraints: `title `.
+    pub fn to_sql(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ty) = &self.ty {
+            parts.push(ty.to_string());
+        }
+        parts.extend(self.constraints.iter().map(ColumnConstraint::to_sql));
+        format!("{} {}", identifier(&self.name), parts.join(" "))
+    }
+}
+
+impl ColumnType {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "integer" => Self::Integer,
+            "float" => Self::Float,
+            "object" => Self::Object,
+            _ => Self::Other(name.to_string()),
+        }
+    }
+
+    /// Return the type of a column declared in SQL, the reverse of
+    /// [`Display`], a column without a type is `None`.
+    pub fn from_sql(decl: &str) -> Option<Self> {
+        match decl {
+            "" => None,
+            "INTEGER" => Some(Self::Integer),
+            "REAL" => Some(Self::Float),
+            "TEXT" => Some(Self::Object),
+            _ => Some(Self::Other(decl.to_string())),
+        }
+    }
+
+    /// Return true for a column of plain text like `(scheduled text)`.
+    ///
+    /// EmacSQL declares `object` columns as `TEXT`, so only other spellings
+    /// like `text` mean text which isn't lisp.
+    pub fn is_text(&self) -> bool {
+        matches!(self, Self::Other(name) if name.eq_ignore_ascii_case("text"))
+    }
+}
+
+impl Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Integer => f.write_str("INTEGER"),
+            Self::Float => f.write_str("REAL"),
+            Self::Object => f.write_str("TEXT"),
+            Self::Other(name) => f.write_str(name),
+        }
+    }
+}
+
+impl ColumnConstraint {
+    pub fn to_sql(&self) -> String {
+        match self {
+            Self::NotNull => "NOT NULL".to_string(),
+            Self::PrimaryKey => "PRIMARY KEY".to_string(),
+            Self::Unique => "UNIQUE".to_string(),
+            Self::Autoincrement => "AUTOINCREMENT".to_string(),
+            Self::Default(val) => format!("DEFAULT {}", expr_sql(val)),
+            Self::Check(val) => format!("CHECK ({})", expr_sql(val)),
+        }
+    }
+}
+
+impl TableConstraint {
+    /// Parse a constraint like `(:foreign-key [file] :references files [file])`.
+    pub fn parse(def: &lisp::Value) -> Result<Self> {
+        let lisp::Value::List(items) = def else {
+            return Err(invalid(def));
+        };
+        let constraint = match items.as_slice() {
+            [kw, columns] if keyword_of(kw) == Some("primary-key") => {
+                Self::PrimaryKey(names(columns)?)
+            }
+            [kw, columns] if keyword_of(kw) == Some("unique") => Self::Unique(names(columns)?),
+            [kw, expr] if keyword_of(kw) == Some("check") => Self::Check(expr.clone()),
+            [kw, columns, references, lisp::Value::Symbol(table), foreign, actions @ ..]
+                if keyword_of(kw) == Some("foreign-key")
+                    && keyword_of(references) == Some("references") =>
+            {
+                let mut on_delete = None;
+                let mut on_update = None;
+                for pair in actions.chunks(2) {
+                    let [when, action] = pair else {
+                        return Err(invalid(def));
+                    };
+                    let action =
+                        ForeignKeyAction::from_keyword(action).ok_or_else(|| invalid(def))?;
+                    match keyword_of(when) {
+                        Some("on-delete") => on_delete = Some(action),
+                        Some("on-update") => on_update = Some(action),
+                        _ => return Err(invalid(def)),
+                    }
+                }
+                Self::ForeignKey {
+                    columns: names(columns)?,
+                    table: table.clone(),
+                    references: names(foreign)?,
+                    on_delete,
+                    on_update,
+                }
+            }
+            _ => return Err(invalid(def)),
+        };
+        Ok(constraint)
+    }
+
+    pub fn to_sql(&self) -> String {
+        match self {
+            Self::PrimaryKey(columns) => format!("PRIMARY KEY ({})", column_list(columns)),
+            Self::Unique(columns) => format!("UNIQUE ({})", column_list(columns)),
+            Self::Check(expr) => format!("CHECK ({})", expr_sql(expr)),
+            Self::ForeignKey {
+                columns,
+                table,
+                references,
+                on_delete,
+                on_update,
+            } => {
+                let mut sql = format!(
+                    "FOREIGN KEY ({}) REFERENCES {} ({})",
+                    column_list(columns),
+                    identifier(table),
+                    column_list(references)
+                );
+                if let Some(action) = on_delete {
+                    sql += &format!(" ON DELETE {action}");
+                }
+                if let Some(action) = on_update {
+                    sql += &format!(" ON UPDATE {action}");
+                }
+                sql
+            }
+        }
+    }
+}
+
+impl ForeignKeyAction {
+    fn from_keyword(val: &lisp::Value) -> Option<Self> {
+        match keyword_of(val)? {
+            "set-null" => Some(Self::SetNull),
+            "set-default" => Some(Self::SetDefault),
+            "cascade" => Some(Self::Cascade),
+            "restrict" => Some(Self::Restrict),
+            "no-action" => Some(Self::NoAction),
+            _ => None,
+        }
+    }
+}
+
+impl Display for ForeignKeyAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::SetNull => "SET NULL",
+            Self::SetDefault => "SET DEFAULT",
+            Self::Cascade => "CASCADE",
+            Self::Restrict => "RESTRICT",
+            Self::NoAction => "NO ACTION",
+        })
+    }
+}
+
+impl Index {
+    /// Return the `CREATE INDEX` statement of this index.
+    ///
+    /// EmacSQL writes the columns like column definitions, so they are
+    /// followed by a space too: `(node_id )`.
+    pub fn to_sql(&self) -> String {
+        let columns: Vec<_> = self
+            .columns
+            .iter()
+            .map(|col| format!("{} ", identifier(col)))
+            .collect();
+        format!(
+            "CREATE INDEX {} ON {} ({})",
+            identifier(&self.name),
+            identifier(&self.table),
+            columns.join(", ")
+        )
+    }
+}
+
+/// The declared types of the columns which a statement selects, see
+/// [`crate::Row::column_types`].
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct ColumnTypes {
+    types: Vec<Option<ColumnType>>,
+}
+
+impl ColumnTypes {
+    /// Read the declared types of the columns of a statement which reads
+    /// `table`, see [`ColumnTypes::of_statement`].
+    pub fn new(table: &Table, stmt: &Statement) -> Self {
+        Self::declared(Some(table), stmt)
+    }
+
+    /// Read the declared types of the columns of a statement.
+    ///
+    /// A computed column, like `count(*)`, has no type, and neither do the
+    /// columns of a compound select, whose rows come from several tables.
+    /// SQLite reports `text` as `TEXT` like the type of `object` columns,
+    /// so the spelling is read from the table of the statement, see
+    /// [`Table::introspect`].
+    ///
+    /// The table is found by reading the SQL of the statement, not by asking
+    /// SQLite where each column comes from, so this is a best effort: a
+    /// `text` column of a join or of a subquery in `FROM` is an `object`, and
+    /// a column renamed with `AS` is only typed when the columns with the
+    /// same reported type have one spelling.
+    pub fn of_statement(conn: &Connection, stmt: &Statement) -> crate::Result<Self> {
+        let Some(sql) = stmt.expanded_sql() else {
+            return Ok(Self::default());
+        };
+        if is_compound(&sql) {
+            return Ok(Self::default());
+        }
+        let table = match single_table(&sql) {
+            Some(name) => Table::introspect(conn, &name)?,
+            None => None,
+        };
+        Ok(Self::declared(table.as_ref(), stmt))
+    }
+
+    fn declared(table: Option<&Table>, stmt: &Statement) -> Self {
+        let types = stmt
+            .columns()
+            .iter()
+            .map(|column| {
+                let decl = column.decl_type()?;
+                let spelled =
+                    table.and_then(|table| spelled_column_type(table, column.name(), decl));
+                spelled.or_else(|| ColumnType::from_sql(decl))
+            })
+            .collect();
+        Self { types }
+    }
+
+    /// Return the type of the column with the index `idx`.
+    pub fn get(&self, idx: usize) -> Option<&ColumnType> {
+        self.types.get(idx)?.as_ref()
+    }
+}
+
+/// Return the type of the column `name` of `table`, which SQLite reports as
+/// `decl`.
+///
+/// A column renamed with `AS` isn't found by its name, but its type is still
+/// known, when all the columns with the same reported type have one
+/// spelling.
+fn spelled_column_type(table: &Table, name: &str, decl: &str) -> Option<ColumnType> {
+    let mut candidates = table.columns.iter().filter(|col| {
+        col.ty
+            .as_ref()
+            .is_some_and(|ty| ty.to_string().eq_ignore_ascii_case(decl))
+    });
+    if let Some(col) = candidates
+        .clone()
+        .find(|col| col.name.eq_ignore_ascii_case(name))
+    {
+        return col.ty.clone();
+    }
+    let ty = candidates.next()?.ty.as_ref();
+    candidates
+        .all(|col| col.ty.as_ref() == ty)
+        .then(|| ty.cloned())
+        .flatten()
+}
+
+/// Return the type of a column as it's written in `CREATE TABLE`.
+///
+/// SQLite reports both `text` and `TEXT` as `TEXT`, but EmacSQL writes
+/// `object` columns as `TEXT` and leaves `text` of org-roam as it is.
+fn spelled_type<'a>(create: &'a str, column: &str) -> Option<&'a str> {
+    let defs = &create[create.find('(')? + 1..create.rfind(')')?];
+    // columns are defined before table constraints, so a part of a
+    // constraint split by a comma isn't found first
+    defs.split(',').find_map(|def| {
+        let mut words = def.split_whitespace();
+        let name = words
+            .next()?
+            .trim_matches(|c| matches!(c, '"' | '`' | '[' | ']'));
+        name.eq_ignore_ascii_case(column)
+            .then(|| words.next())
+            .flatten()
+    })
+}
+
+fn invalid(val: &lisp::Value) -> Error {
+    Error::InvalidSchema(val.clone())
+}
+
+fn keyword_of(val: &lisp::Value) -> Option<&str> {
+    match val {
+        lisp::Value::Symbol(name) => name.strip_prefix(':'),
+        _ => None,
+    }
+}
+
+/// Return the items of a list of lists like `((files [...]) (nodes [...]))`,
+/// it can be quoted like in Elisp source.
+fn entries(val: &lisp::Value) -> Result<Vec<Vec<lisp::Value>>> {
+    let items = match val {
+        lisp::Value::List(items) => match items.as_slice() {
+            [lisp::Value::Symbol(quote), quoted] if quote == "quote" => return entries(quoted),
+            _ => items,
+        },
+        _ => return Err(invalid(val)),
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            lisp::Value::List(entry) => Ok(entry.clone()),
+            _ => Err(invalid(item)),
+        })
+        .collect()
+}
+
+/// Return the names of a vector of columns like `[node-id]`.
+fn names(val: &lisp::Value) -> Result<Vec<String>> {
+    match val {
+        lisp::Value::Vector(items) => items
+            .iter()
+            .map(|item| match item {
+                lisp::Value::Symbol(name) => Ok(name.clone()),
+                _ => Err(invalid(val)),
+            })
+            .collect(),
+        _ => Err(invalid(val)),
+    }
+}
+
+fn column_list(columns: &[String]) -> String {
+    let columns: Vec<_> = columns.iter().map(|col| identifier(col)).collect();
+    columns.join(", ")
+}
+
+/// Compile an expression of a constraint, like `0` in `:default 0`.
+fn expr_sql(val: &lisp::Value) -> String {
+    // expressions of a parsed schema have no parameters, so only invalid
+    // operators can fail, and they are written as they are to let SQLite
+    // report them
+    compile_expr(val).unwrap_or_else(|_| val.to_lisp_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_column() {
+        let col = Column::parse(&"(id text :not-null :default \"x\")".parse().unwrap()).unwrap();
+        assert_eq!(col.name, "id");
+        assert_eq!(col.ty, Some(ColumnType::Other("text".into())));
+        assert_eq!(
+            col.constraints,
+            vec![
+                ColumnConstraint::NotNull,
+                ColumnConstraint::Default(lisp::Value::String("x".into()))
+            ]
+        );
+        assert_eq!(col.to_sql(), "id text NOT NULL DEFAULT '\"x\"'");
+        assert_eq!(
+            Column::parse(&"(level integer :check (>= level 0))".parse().unwrap())
+                .unwrap()
+                .to_sql(),
+            "level INTEGER CHECK (level >= 0)"
+        );
+        assert!(Column::parse(&"(id :wat)".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_org_roam_schema() {
+        let schema = Schema::org_roam();
+        assert_eq!(schema.tables.len(), 7);
+        let nodes = &schema.tables[1];
+        assert_eq!(nodes.columns[0].name, "id");
+        assert_eq!(
+            nodes.constraints,
+            vec![TableConstraint::ForeignKey {
+                columns: vec!["file".into()],
+                table: "files".into(),
+                references: vec!["file".into()],
+                on_delete: Some(ForeignKeyAction::Cascade),
+                on_update: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_create_org_roam_db() {
+        let conn = Connection::open_in_memory().unwrap();
+        Schema::org_roam().create(&conn).unwrap();
+
+        // `sqlite_master` of a database which is created by org-roam
+        let expected = [
+            "CREATE TABLE files (file UNIQUE PRIMARY KEY, title , hash NOT NULL, atime NOT NULL, mtime NOT NULL)",
+            "CREATE TABLE nodes (id NOT NULL PRIMARY KEY, file NOT NULL, level NOT NULL, pos NOT NULL, todo , priority , scheduled text, deadline text, title , properties , olp , FOREIGN KEY (file) REFERENCES files (file) ON DELETE CASCADE)",
+            "CREATE TABLE aliases (node_id NOT NULL, alias , FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE)",
+            "CREATE TABLE citations (node_id NOT NULL, cite_key NOT NULL, pos NOT NULL, properties , FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE)",
+            "CREATE TABLE refs (node_id NOT NULL, ref NOT NULL, type NOT NULL, FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE)",
+            "CREATE TABLE tags (node_id NOT NULL, tag , FOREIGN KEY (node_id) REFERENCES nodes (id) ON DELETE CASCADE)",
+            "CREATE TABLE links (pos NOT NULL, source NOT NULL, dest NOT NULL, type NOT NULL, properties NOT NULL, FOREIGN KEY (source) REFERENCES nodes (id) ON DELETE CASCADE)",
+            "CREATE INDEX alias_node_id ON aliases (node_id )",
+            "CREATE INDEX refs_node_id ON refs (node_id )",
+            "CREATE INDEX tags_node_id ON tags (node_id )",
+        ];
+        let mut stmt = conn
+            .prepare("SELECT sql FROM sqlite_master WHERE sql IS NOT NULL")
+            .unwrap();
+        let actual: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(actual, expected);
+        let version: i64 = conn
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(version, ORG_ROAM_DB_VERSION);
+    }
+
+    #[test]
+    fn test_introspect() {
+        let conn = Connection::open_in_memory().unwrap();
+        Schema::org_roam().create(&conn).unwrap();
+        conn.execute_batch("CREATE TABLE pairs (a INTEGER, b REAL, c TEXT, PRIMARY KEY (b, a))")
+            .unwrap();
+
+        let nodes = Table::introspect(&conn, "nodes").unwrap().unwrap();
+        let expected = &Schema::org_roam().tables[1];
+        assert_eq!(nodes.columns, expected.columns);
+        assert_eq!(nodes.constraints, []);
+
+        let pairs = Table::introspect(&conn, "pairs").unwrap().unwrap();
+        let types: Vec<_> = pairs.columns.iter().map(|col| col.ty.clone()).collect();
+        assert_eq!(
+            types,
+            [
+                Some(ColumnType::Integer),
+                Some(ColumnType::Float),
+                Some(ColumnType::Object)
+            ]
+        );
+        assert_eq!(
+            pairs.constraints,
+            [TableConstraint::PrimaryKey(vec!["b".into(), "a".into()])]
+        );
+        assert_eq!(Table::introspect(&conn, "missing").unwrap(), None);
+
+        let stmt = conn
+            .prepare("SELECT title, scheduled, count(*) FROM nodes WHERE level > 0")
+            .unwrap();
+        let types = ColumnTypes::of_statement(&conn, &stmt).unwrap();
+        assert_eq!(types.get(0), None);
+        assert!(types.get(1).is_some_and(ColumnType::is_text));
+        assert_eq!(types.get(2), None);
+        let text_of = |sql| {
+            let stmt = conn.prepare(sql).unwrap();
+            let types = ColumnTypes::of_statement(&conn, &stmt).unwrap();
+            types.get(0).is_some_and(ColumnType::is_text)
+        };
+        assert!(text_of("SELECT deadline AS d FROM nodes"));
+        assert!(!text_of("SELECT id AS scheduled FROM nodes"));
+        // known limitations: the table of a column isn't found in a join or
+        // a subquery, so `text` columns fall back to `object`
+        assert!(!text_of("SELECT scheduled FROM nodes, files"));
+        assert!(!text_of(
+            "SELECT scheduled FROM (SELECT scheduled FROM nodes)"
+        ));
+        let stmt = conn
+            .prepare("SELECT scheduled FROM nodes UNION ALL SELECT dest FROM links")
+            .unwrap();
+        assert_eq!(
+            ColumnTypes::of_statement(&conn, &stmt).unwrap(),
+            ColumnTypes::default()
+        );
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        assert!(matches!(
+            Schema::parse("((files 1))", "()"),
+            Err(Error::InvalidSchema(_))
+        ));
+        assert!(matches!(Schema::parse("((", "()"), Err(Error::Read(_))));
+    }
+}
@@ -0,0 +1,494 @@
+//! Serialize rust values into Emacs Lisp values with `serde`.
+//!
+//! The output is the mirror of [`crate::de`]: sequences become lists, maps
+//! become alists, enum variants become symbols or lists which start with the
+//! name of a variant, and structs become plists or alists (see
+//! [`StructStyle`]).
+
+use std::fmt::Display;
+
+use serde::ser::{self, Error as _, Serialize};
+
+pub use crate::de::Error;
+use crate::de::Result;
+use crate::lisp::Value;
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        <Error as serde::de::Error>::custom(msg)
+    }
+}
+
+/// The shape of a serialized struct.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StructStyle {
+    /// `(:id "x" :level 1)`
+    #[default]
+    Plist,
+    /// `((id . "x") (level . 1))`
+    SymbolAlist,
+    /// `(("id" . "x") ("level" . 1))`, like the `properties` of org-roam nodes
+    StringAlist,
+}
+
+/// Serialize a value into a lisp value, structs become plists.
+pub fn to_value<T: Serialize + ?Sized>(value: &T) -> Result<Value> {
+    value.serialize(Serializer::default())
+}
+
+/// Serialize a value into the text which `read` in Emacs understands,
+/// structs become plists.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String> {
+    to_value(value).map(|sexp| sexp.to_lisp_string())
+}
+
+/// Serialize a value into a value which can be bound as a parameter of an
+/// EmacSQL query.
+pub fn to_emacsql<T: Serialize + ?Sized>(value: &T) -> Result<crate::Value> {
+    to_value(value).map(crate::Value::Lisp)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Serializer {
+    struct_style: StructStyle,
+}
+
+impl Serializer {
+    pub fn new(struct_style: StructStyle) -> Self {
+        Serializer { struct_style }
+    }
+
+    /// Return the items which represent a field of a struct.
+    fn field(&self, key: &'static str, value: Value) -> Vec<Value> {
+        match self.struct_style {
+            StructStyle::Plist => vec![Value::Symbol(format!(":{key}")), value],
+            StructStyle::SymbolAlist => vec![Value::cons(Value::Symbol(key.to_string()), value)],
+            StructStyle::StringAlist => vec![Value::cons(Value::String(key.to_string()), value)],
+        }
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeAlist;
+    type SerializeStruct = SerializeStruct;
+    type SerializeStructVariant = SerializeStruct;
+
+    fn serialize_bool(self, v: bool) -> Result<Value> {
+        Ok(if v {
+            Value::Symbol("t".to_string())
+        } else {
+            Value::Nil
+        })
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value> {
+        Ok(Value::Integer(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value> {
+        Ok(i64::try_from(v).map_or_else(|_| Value::BigInt(v.to_string()), Value::Integer))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value> {
+        self.serialize_i64(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value> {
+        self.serialize_i128(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value> {
+        Ok(i64::try_from(v).map_or_else(|_| Value::BigInt(v.to_string()), Value::Integer))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value> {
+        self.serialize_f64(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value> {
+        Ok(Value::Real(v))
+    }
+
+    // Emacs represents characters as integers
+    fn serialize_char(self, v: char) -> Result<Value> {
+        Ok(Value::Integer(v as i64))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value> {
+        match String::from_utf8(v.to_vec()) {
+            Ok(s) => Ok(Value::String(s)),
+            Err(err) => Ok(Value::Unibyte(err.into_bytes())),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Value> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value> {
+        Ok(Value::Nil)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value> {
+        Ok(Value::Symbol(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value> {
+        Ok(Value::List(vec![
+            Value::Symbol(variant.to_string()),
+            value.serialize(self)?,
+        ]))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList> {
+        Ok(SerializeList {
+            ser: self,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeList> {
+        let mut items = Vec::with_capacity(len + 1);
+        items.push(Value::Symbol(variant.to_string()));
+        Ok(SerializeList { ser: self, items })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<SerializeAlist> {
+        Ok(SerializeAlist {
+            ser: self,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeStruct> {
+        Ok(SerializeStruct {
+            ser: self,
+            items: Vec::with_capacity(len * 2),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeStruct> {
+        let mut items = Vec::with_capacity(len * 2 + 1);
+        items.push(Value::Symbol(variant.to_string()));
+        Ok(SerializeStruct { ser: self, items })
+    }
+}
+
+pub struct SerializeList {
+    ser: Serializer,
+    items: Vec<Value>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.items.push(value.serialize(self.ser)?);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+/// Maps are serialized as alists: `((key . value) ...)`.
+pub struct SerializeAlist {
+    ser: Serializer,
+    items: Vec<Value>,
+    key: Option<Value>,
+}
+
+impl ser::SerializeMap for SerializeAlist {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(key.serialize(self.ser)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::custom("serialize_value is called before serialize_key"))?;
+        self.items
+            .push(Value::cons(key, value.serialize(self.ser)?));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+pub struct SerializeStruct {
+    ser: Serializer,
+    items: Vec<Value>,
+}
+
+impl ser::SerializeStruct for SerializeStruct {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let value = value.serialize(self.ser)?;
+        self.items.extend(self.ser.field(key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeStruct {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        ser::SerializeStruct::serialize_field(self, key, value)
+    }
+
+    fn end(self) -> Result<Value> {
+        Ok(Value::list(self.items))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::de::from_str;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Node {
+        id: String,
+        level: usize,
+        tags: Vec<String>,
+        todo: Option<String>,
+        archived: bool,
+    }
+
+    fn node() -> Node {
+        Node {
+            id: "1".into(),
+            level: 2,
+            tags: vec!["physics".into(), "math".into()],
+            todo: None,
+            archived: true,
+        }
+    }
+
+    #[test]
+    fn test_struct_styles() {
+        assert_eq!(
+            to_string(&node()).unwrap(),
+            r#"(:id "1" :level 2 :tags ("physics" "math") :todo nil :archived t)"#
+        );
+
+        let alist = node().serialize(Serializer::new(StructStyle::SymbolAlist));
+        assert_eq!(
+            alist.unwrap().to_lisp_string(),
+            r#"((id . "1") (level . 2) (tags "physics" "math") (todo) (archived . t))"#
+        );
+
+        let alist = node().serialize(Serializer::new(StructStyle::StringAlist));
+        assert_eq!(
+            alist.unwrap().to_lisp_string(),
+            r#"(("id" . "1") ("level" . 2) ("tags" "physics" "math") ("todo") ("archived" . t))"#
+        );
+    }
+
+    #[test]
+    fn test_round_trip_through_deserializer() {
+        for style in [
+            StructStyle::Plist,
+            StructStyle::SymbolAlist,
+            StructStyle::StringAlist,
+        ] {
+            let text = node()
+                .serialize(Serializer::new(style))
+                .unwrap()
+                .to_lisp_string();
+            assert_eq!(from_str::<Node>(&text), Ok(node()), "{style:?}: {text}");
+        }
+    }
+
+    #[test]
+    fn test_maps_and_enums() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        #[serde(rename_all = "kebab-case")]
+        enum Shape {
+            Empty,
+            Circle(f64),
+            Point(i64, i64),
+            Named { name: String },
+        }
+
+        let shapes = vec![
+            Shape::Empty,
+            Shape::Circle(1.0),
+            Shape::Point(1, 2),
+            Shape::Named { name: "x".into() },
+        ];
+        let text = to_string(&shapes).unwrap();
+        assert_eq!(
+            text,
+            r#"(empty (circle 1.0) (point 1 2) (named :name "x"))"#
+        );
+        assert_eq!(from_str::<Vec<Shape>>(&text), Ok(shapes));
+
+        let map = BTreeMap::from([("CATEGORY", "physics"), ("ID", "1")]);
+        assert_eq!(
+            to_string(&map).unwrap(),
+            r#"(("CATEGORY" . "physics") ("ID" . "1"))"#
+        );
+    }
+
+    #[test]
+    fn test_scalars() {
+        assert_eq!(to_string("a \"b\"").unwrap(), r#""a \"b\"""#);
+        assert_eq!(to_string(&'a').unwrap(), "97");
+        assert_eq!(to_string(&()).unwrap(), "nil");
+        assert_eq!(to_string(&(1, "x")).unwrap(), r#"(1 "x")"#);
+        assert_eq!(to_string(&u64::MAX).unwrap(), "18446744073709551615");
+    }
+}
@@ -0,0 +1,394 @@
+//! Emacs time values.
+//!
+//! org-roam stores `files.atime` and `files.mtime` as Emacs time lists
+//! `(HIGH LOW USEC PSEC)`, the number of seconds is `HIGH * 65536 + LOW`.
+//! Emacs also accepts an integer or a float number of seconds and
+//! `(TICKS . HZ)` as a time, all of them are read.
+//!
+//! `nodes.scheduled` and `nodes.deadline` are strings made with
+//! `(format-time-string "%FT%T%z")`, like `2023-04-05T10:00:00+0300`.  Org
+//! timestamps like `<2023-04-05 Wed 10:00>` are read too, they have no time
+//! zone, so they are read as UTC.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::error::FromEmacsqlError;
+use crate::lisp;
+use crate::params::ToLisp;
+use crate::value::FromLisp;
+
+const NANOS_PER_SEC: i128 = 1_000_000_000;
+
+/// Return the number of seconds and nanoseconds since the epoch of an Emacs
+/// time value, nanoseconds are always positive.
+fn time_value(sexp: &lisp::Value) -> Option<(i64, u32)> {
+    let int = |val: &lisp::Value| match val {
+        lisp::Value::Integer(n) => Some(*n as i128),
+        _ => None,
+    };
+    let nanos = match sexp {
+        lisp::Value::Integer(secs) => *secs as i128 * NANOS_PER_SEC,
+        lisp::Value::Real(secs) if secs.is_finite() => (secs * 1e9).round() as i128,
+        lisp::Value::List(items) if (2..=4).contains(&items.len()) => {
+            let mut parts = items.iter().map(int);
+            let high = parts.next()??;
+            let low = parts.next()??;
+            let usec = parts.next().unwrap_or(Some(0))?;
+            let psec = parts.next().unwrap_or(Some(0))?;
+            (high * 65536 + low) * NANOS_PER_SEC + usec * 1000 + psec / 1000
+        }
+        lisp::Value::Cons(ticks, hz) => {
+            let hz = int(hz).filter(|hz| *hz > 0)?;
+            int(ticks)? * NANOS_PER_SEC / hz
+        }
+        _ => return None,
+    };
+    let secs = i64::try_from(nanos.div_euclid(NANOS_PER_SEC)).ok()?;
+    Some((secs, nanos.rem_euclid(NANOS_PER_SEC) as u32))
+}
+
+fn system_time(secs: i64, nanos: u32) -> Option<SystemTime> {
+    let since_epoch = if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs as u64))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    };
+    since_epoch?.checked_add(Duration::from_nanos(nanos.into()))
+}
+
+/// Return the Emacs time list `(HIGH LOW USEC PSEC)` of a time.
+fn time_list(secs: i64, nanos: u32) -> lisp::Value {
+    let int = lisp::Value::Integer;
+    lisp::Value::List(vec![
+        int(secs.div_euclid(65536)),
+        int(secs.rem_euclid(65536)),
+        int((nanos / 1000).into()),
+        int((nanos % 1000 * 1000).into()),
+    ])
+}
+
+/// A date and a time of day from a timestamp string, `offset` is the
+/// difference with UTC in seconds, if a timestamp has it.
+#[derive(Debug, PartialEq)]
+struct Timestamp {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+    offset: Option<i64>,
+}
+
+impl Timestamp {
+    /// Parse `2023-04-05T10:00:00+0300` or an org timestamp like
+    /// `<2023-04-05 Wed 10:00 +1w>`.
+    fn parse(s: &str) -> Option<Self> {
+        let s = s.trim();
+        let s = match (s.chars().next()?, s.chars().last()?) {
+            ('<', '>') | ('[', ']') => &s[1..s.len() - 1],
+            _ => s,
+        };
+        let (year, s) = split_number(s, 4)?;
+        let (month, s) = split_number(s.strip_prefix('-')?, 2)?;
+        let (day, s) = split_number(s.strip_prefix('-')?, 2)?;
+        let mut ts = Self {
+            year: year.into(),
+            month,
+            day,
+            hour: 0,
+            minute: 0,
+            second: 0,
+            offset: None,
+        };
+
+        if let Some(s) = s.strip_prefix('T') {
+            let (hour, s) = split_number(s, 2)?;
+            let (minute, s) = split_number(s.strip_prefix(':')?, 2)?;
+            let (second, s) = match s.strip_prefix(':') {
+                Some(s) => split_number(s, 2)?,
+                None => (0, s),
+            };
+            (ts.hour, ts.minute, ts.second) = (hour, minute, second);
+            ts.offset = parse_offset(s)?;
+        } else {
+            for word in s.split_whitespace() {
+                match word.chars().next()? {
+                    // a repeater or a warning period like `+1w` or `-2d`
+                    '+' | '-' | '.' => {}
+                    ch if ch.is_ascii_digit() => {
+                        // the start of a time range like `10:00-11:00`
+                        let (hour, rest) = split_number(word, 2).or(split_number(word, 1))?;
+                        let (minute, _) = split_number(rest.strip_prefix(':')?, 2)?;
+                        (ts.hour, ts.minute) = (hour, minute);
+                    }
+                    // the name of a day
+                    _ => {}
+                }
+            }
+        }
+
+        let valid = (1..=12).contains(&ts.month)
+            && (1..=31).contains(&ts.day)
+            && ts.hour < 24
+            && ts.minute < 60
+            && ts.second <= 60;
+        valid.then_some(ts)
+    }
+
+    /// Return the number of seconds since the epoch.
+    fn unix_seconds(&self) -> i64 {
+        let days = days_from_civil(self.year, self.month, self.day);
+        let secs = self.hour * 3600 + self.minute * 60 + self.second;
+        days * 86400 + i64::from(secs) - self.offset.unwrap_or(0)
+    }
+}
+
+/// Split a number of exactly `len` ASCII digits from the start of `s`.
+fn split_number(s: &str, len: usize) -> Option<(u32, &str)> {
+    let digits = s.get(..len)?;
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    Some((digits.parse().ok()?, &s[len..]))
+}
+
+/// Parse the time zone of a timestamp: nothing, `Z`, `+0300` or `+03:00`.
+fn parse_offset(s: &str) -> Option<Option<i64>> {
+    let sign = match s.chars().next() {
+        None => return Some(None),
+        Some('Z') if s.len() == 1 => return Some(Some(0)),
+        Some('+') => 1,
+        Some('-') => -1,
+        Some(_) => return None,
+    };
+    let (hours, s) = split_number(&s[1..], 2)?;
+    let s = s.strip_prefix(':').unwrap_or(s);
+    let (minutes, s) = split_number(s, 2)?;
+    if !s.is_empty() {
+        return None;
+    }
+    Some(Some(sign * i64::from(hours * 3600 + minutes * 60)))
+}
+
+/// Return the number of days since 1970-01-01 of a date in the proleptic
+/// Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = i64::from(month);
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// A time list, a number of seconds or a timestamp string.
+impl FromLisp for SystemTime {
+    fn from_lisp(sexp: lisp::Value) -> Result<Self, FromEmacsqlError> {
+        let (secs, nanos) = match &sexp {
+            lisp::Value::String(s) | lisp::Value::Propertized(s, _) => {
+                let ts = Timestamp::parse(s).ok_or(FromEmacsqlError::InvalidType)?;
+                (ts.unix_seconds(), 0)
+            }
+            sexp => time_value(sexp).ok_or(FromEmacsqlError::InvalidType)?,
+        };
+        system_time(secs, nanos).ok_or(FromEmacsqlError::InvalidType)
+    }
+}
+
+/// The time list `(HIGH LOW USEC PSEC)`, like `current-time` in Emacs.
+impl ToLisp for SystemTime {
+    fn to_lisp(&self) -> lisp::Value {
+        let (secs, nanos) = match self.duration_since(UNIX_EPOCH) {
+            Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+            Err(err) => {
+                let before = err.duration();
+                match before.subsec_nanos() {
+                    0 => (-(before.as_secs() as i64), 0),
+                    nanos => (-(before.as_secs() as i64) - 1, 1_000_000_000 - nanos),
+                }
+            }
+        };
+        time_list(secs, nanos)
+    }
+}
+
+#[cfg(feature = "chrono")]
+mod chrono_impls {
+    use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+    use super::*;
+
+    fn invalid<T>(_: T) -> FromEmacsqlError {
+        FromEmacsqlError::InvalidType
+    }
+
+    /// A time list, a number of seconds or a timestamp string.
+    impl FromLisp for DateTime<Utc> {
+        fn from_lisp(sexp: lisp::Value) -> Result<Self, FromEmacsqlError> {
+            SystemTime::from_lisp(sexp).map(DateTime::from)
+        }
+    }
+
+    /// Timestamp strings keep their offset, other times are in UTC.
+    impl FromLisp for DateTime<FixedOffset> {
+        fn from_lisp(sexp: lisp::Value) -> Result<Self, FromEmacsqlError> {
+            match &sexp {
+                lisp::Value::String(s) | lisp::Value::Propertized(s, _) => {
+                    let ts = Timestamp::parse(s).ok_or(FromEmacsqlError::InvalidType)?;
+                    let offset = ts.offset.unwrap_or(0) as i32;
+                    let offset =
+                        FixedOffset::east_opt(offset).ok_or(FromEmacsqlError::InvalidType)?;
+                    offset
+                        .timestamp_opt(ts.unix_seconds(), 0)
+                        .single()
+                        .ok_or(FromEmacsqlError::InvalidType)
+                }
+                _ => DateTime::<Utc>::from_lisp(sexp).map(|time| time.fixed_offset()),
+            }
+        }
+    }
+
+    /// The date and the time of a timestamp string as they are written.
+    impl FromLisp for NaiveDateTime {
+        fn from_lisp(sexp: lisp::Value) -> Result<Self, FromEmacsqlError> {
+            let s = String::from_lisp(sexp)?;
+            let ts = Timestamp::parse(&s).ok_or(FromEmacsqlError::InvalidType)?;
+            NaiveDate::from_ymd_opt(ts.year.try_into().map_err(invalid)?, ts.month, ts.day)
+                .and_then(|date| date.and_hms_opt(ts.hour, ts.minute, ts.second))
+                .ok_or(FromEmacsqlError::InvalidType)
+        }
+    }
+
+    impl FromLisp for NaiveDate {
+        fn from_lisp(sexp: lisp::Value) -> Result<Self, FromEmacsqlError> {
+            NaiveDateTime::from_lisp(sexp).map(|time| time.date())
+        }
+    }
+
+    /// The time list `(HIGH LOW USEC PSEC)`.
+    impl ToLisp for DateTime<Utc> {
+        fn to_lisp(&self) -> lisp::Value {
+            time_list(self.timestamp(), self.timestamp_subsec_nanos())
+        }
+    }
+
+    /// The string like `2023-04-05T10:00:00+0300`, like org-roam writes
+    /// `nodes.scheduled`.
+    impl ToLisp for DateTime<FixedOffset> {
+        fn to_lisp(&self) -> lisp::Value {
+            lisp::Value::String(self.format("%FT%T%z").to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(src: &str) -> Result<SystemTime, FromEmacsqlError> {
+        SystemTime::from_lisp(src.parse().unwrap())
+    }
+
+    fn since_epoch(secs: i64, nanos: u32) -> SystemTime {
+        system_time(secs, nanos).unwrap()
+    }
+
+    #[test]
+    fn test_time_lists() {
+        // 1680685200 is 2023-04-05T09:00:00Z
+        assert_eq!(time("(25645 14480)"), Ok(since_epoch(1680685200, 0)));
+        assert_eq!(
+            time("(25645 14480 123456 789000)"),
+            Ok(since_epoch(1680685200, 123456789))
+        );
+        assert_eq!(time("1680685200"), Ok(since_epoch(1680685200, 0)));
+        assert_eq!(time("1.5"), Ok(since_epoch(1, 500_000_000)));
+        assert_eq!(time("(3 . 2)"), Ok(since_epoch(1, 500_000_000)));
+        assert_eq!(
+            time("(-1 65535 500000 0)"),
+            Ok(since_epoch(-1, 500_000_000))
+        );
+        assert!(time("(1 2 3 4 5)").is_err());
+        assert!(time("(1 . 0)").is_err());
+    }
+
+    #[test]
+    fn test_time_to_lisp() {
+        for src in [
+            "(25645 14480 123456 789000)",
+            "(-1 65535 500000 0)",
+            "(0 0 0 0)",
+        ] {
+            let time = time(src).unwrap();
+            assert_eq!(time.to_lisp().to_lisp_string(), src);
+        }
+    }
+
+    #[test]
+    fn test_timestamps() {
+        assert_eq!(
+            time("\"2023-04-05T12:00:00+0300\""),
+            Ok(since_epoch(1680685200, 0))
+        );
+        assert_eq!(
+            time("\"2023-04-05T09:00:00Z\""),
+            Ok(since_epoch(1680685200, 0))
+        );
+        assert_eq!(
+            time("\"<2023-04-05 Wed 09:00 +1w>\""),
+            Ok(since_epoch(1680685200, 0))
+        );
+        assert_eq!(
+            time("\"[2023-04-05 Wed 9:00-10:30]\""),
+            Ok(since_epoch(1680685200, 0))
+        );
+        assert_eq!(time("\"1969-12-31\""), Ok(since_epoch(-86400, 0)));
+        assert!(time("\"2023-13-05\"").is_err());
+        assert!(time("\"yesterday\"").is_err());
+    }
+
+    #[test]
+    fn test_sort_files_by_mtime() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (file, mtime);
+INSERT INTO files VALUES ('"old.org"', '(25000 0 0 0)'), ('"new.org"', '(25645 14480 0 0)');
+"#,
+        )
+        .unwrap();
+        let mut files: Vec<(String, SystemTime)> = conn
+            .prepare("SELECT file, mtime FROM files")
+            .unwrap()
+            .query_map([], |row| {
+                let row = crate::Row::from(row);
+                Ok((row.get("file")?, row.get("mtime")?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        files.sort_by_key(|(_, mtime)| std::cmp::Reverse(*mtime));
+        assert_eq!(files[0].0, "new.org");
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn test_chrono() {
+        use chrono::{DateTime, FixedOffset, NaiveDate, Utc};
+
+        let val: lisp::Value = "\"2023-04-05T12:00:00+0300\"".parse().unwrap();
+        let time = DateTime::<FixedOffset>::from_lisp(val.clone()).unwrap();
+        assert_eq!(time.to_rfc3339(), "2023-04-05T12:00:00+03:00");
+        assert_eq!(time.to_lisp(), val);
+
+        let utc = DateTime::<Utc>::from_lisp("(25645 14480 0 0)".parse().unwrap()).unwrap();
+        assert_eq!(utc, time);
+        assert_eq!(utc.to_lisp().to_lisp_string(), "(25645 14480 0 0)");
+
+        let date = NaiveDate::from_lisp("\"<2023-04-05 Wed>\"".parse().unwrap()).unwrap();
+        assert_eq!(date, NaiveDate::from_ymd_opt(2023, 4, 5).unwrap());
+    }
+}
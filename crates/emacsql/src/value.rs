@@ -1,11 +1,16 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::hash::BuildHasher;
 use std::str::FromStr;
 
 use crate::error::FromEmacsqlError;
 use crate::lisp;
+use crate::schema::ColumnType;
 
 use rusqlite::{
     self,
-    types::{FromSql, FromSqlError},
+    types::{FromSql, FromSqlResult, ValueRef},
 };
 
 // look up `emacsql-type-map` in the emacs editor to check available types of
@@ -14,36 +19,121 @@ use rusqlite::{
 // In emacsql README.md you could readed that every value in emacsql database will represents as
 // string or null, but is myth.  Emacsql also represents numbers and floats with respective types
 
+#[derive(PartialEq, Clone, Debug)]
 pub enum Value {
     Lisp(lisp::Value),
     Integer(i64),
     Real(f64),
     Null,
-}
-
-impl Value {
-    fn is_null(&self) -> bool {
-        matches!(self, Self::Null)
-    }
+    /// EmacSQL never writes blobs, but other programs can
+    Blob(Vec<u8>),
 }
 
 type FromEmacsqlResult<T> = Result<T, FromEmacsqlError>;
 
-// from sqlite::Value to Value
-impl FromSql for Value {
-    fn column_result(value: rusqlite::types::ValueRef<'_>) -> rusqlite::types::FromSqlResult<Self> {
-        use rusqlite::types::ValueRef::*;
+/// What to do with a text column which isn't valid UTF-8.
+///
+/// Emacs writes raw bytes of multibyte strings as they are, so a column can
+/// hold any bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvalidText {
+    /// keep the bytes, a string with them is read as [`lisp::Value::Unibyte`]
+    #[default]
+    Raw,
+    /// replace invalid bytes with U+FFFD
+    Lossy,
+    /// fail to decode a column
+    Error,
+    /// like `Error`, but [`crate::RowsAs`] skips rows with such columns
+    SkipRow,
+}
 
+impl Value {
+    /// Convert a column, its text which isn't UTF-8 is handled according to
+    /// `policy`.
+    pub fn from_sql_with(value: ValueRef<'_>, policy: InvalidText) -> FromSqlResult<Self> {
         match value {
-            Null => Ok(Self::Null),
-            Text(s) => Ok(Self::Lisp(std::str::from_utf8(s).unwrap().parse()?)),
+            ValueRef::Null => Ok(Self::Null),
+            ValueRef::Text(bytes) => {
+                let text = match (std::str::from_utf8(bytes), policy) {
+                    (Ok(text), _) => Cow::Borrowed(text),
+                    (Err(_), InvalidText::Raw) => Cow::Owned(escape_raw_bytes(bytes)),
+                    (Err(_), InvalidText::Lossy) => String::from_utf8_lossy(bytes),
+                    (Err(err), InvalidText::Error | InvalidText::SkipRow) => {
+                        return Err(FromEmacsqlError::Other(Box::new(err)))
+                    }
+                };
+                Ok(Self::Lisp(text.parse()?))
+            }
             // In EmacSQL value cannot be a integer, it's only a text that represents an
             // Emacs lisp or Null that represents nil
-            Real(n) => Ok(Self::Real(n)),
-            Integer(n) => Ok(Self::Integer(n)),
-            Blob(_) => unreachable!("Blob cannot be represented in EmacSQL database"),
+            ValueRef::Real(n) => Ok(Self::Real(n)),
+            ValueRef::Integer(n) => Ok(Self::Integer(n)),
+            ValueRef::Blob(bytes) => Ok(Self::Blob(bytes.to_vec())),
         }
     }
+
+    /// Convert a column declared with the type `ty`.
+    ///
+    /// Text of a plain text column, like `nodes.scheduled` of org-roam, isn't
+    /// read as lisp, unless it's a string literal written by EmacSQL.  Other
+    /// columns are converted like [`Value::from_sql_with`] does.
+    pub fn from_sql_typed(
+        value: ValueRef<'_>,
+        policy: InvalidText,
+        ty: Option<&ColumnType>,
+    ) -> FromSqlResult<Self> {
+        let bytes = match value {
+            ValueRef::Text(bytes) if ty.is_some_and(ColumnType::is_text) => bytes,
+            value => return Self::from_sql_with(value, policy),
+        };
+        let text = match (std::str::from_utf8(bytes), policy) {
+            (Ok(text), _) => plain_text(text).into_owned(),
+            (Err(_), InvalidText::Raw) => {
+                return Ok(Self::Lisp(lisp::Value::Unibyte(bytes.to_vec())))
+            }
+            (Err(_), InvalidText::Lossy) => {
+                plain_text(&String::from_utf8_lossy(bytes)).into_owned()
+            }
+            (Err(err), InvalidText::Error | InvalidText::SkipRow) => {
+                return Err(FromEmacsqlError::Other(Box::new(err)))
+            }
+        };
+        Ok(Self::Lisp(lisp::Value::String(text)))
+    }
+}
+
+/// Return the string of a plain text column, EmacSQL writes it as a string
+/// literal, other programs write it as it is.
+pub(crate) fn plain_text(text: &str) -> Cow<'_, str> {
+    if text.starts_with('"') {
+        if let Some(string) = lisp::parse_string_literal(text) {
+            return string;
+        }
+    }
+    Cow::Borrowed(text)
+}
+
+/// Replace bytes which aren't UTF-8 with octal escapes like `\377`.
+///
+/// `prin1` writes raw bytes only inside of string literals, where the lisp
+/// reader reads such an escape back as the same raw byte.
+fn escape_raw_bytes(bytes: &[u8]) -> String {
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        for byte in chunk.invalid() {
+            write!(text, "\\{byte:03o}").unwrap();
+        }
+    }
+    text
+}
+
+// from sqlite::Value to Value
+impl FromSql for Value {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        Self::from_sql_with(value, InvalidText::default())
+    }
 }
 
 // from Value to any Type
@@ -51,26 +141,60 @@ pub trait FromEmacsql: Sized {
     fn from_emacsql(val: Value) -> FromEmacsqlResult<Self>;
 }
 
-impl<T: FromEmacsql> FromEmacsql for Option<T> {
+/// Convert an Emacs Lisp object into a rust value.
+///
+/// Every `FromLisp` type is also [`FromEmacsql`]: NULL is read as `nil` and
+/// numbers of a column are read as lisp numbers.
+pub trait FromLisp: Sized {
+    fn from_lisp(s: lisp::Value) -> FromEmacsqlResult<Self>;
+}
+
+impl<T: FromLisp> FromEmacsql for T {
     fn from_emacsql(val: Value) -> FromEmacsqlResult<Self> {
-        if val.is_null() {
-            return Ok(None);
-        }
-        T::from_emacsql(val).map(Some)
+        T::from_lisp(val.into())
     }
 }
 
-impl FromEmacsql for bool {
+impl FromEmacsql for Value {
     fn from_emacsql(val: Value) -> FromEmacsqlResult<Self> {
+        Ok(val)
+    }
+}
+
+/// NULL is `nil`, numbers are lisp numbers and blobs are unibyte strings.
+impl From<Value> for lisp::Value {
+    fn from(val: Value) -> Self {
         match val {
-            Value::Null => Ok(false),
-            Value::Lisp(lisp::Value::String(s)) if s == "t" => Ok(true),
-            _ => {
-                if let Value::Lisp(sexp) = val {
-                    println!("try convert lisp value: {:?} to bool", sexp);
-                }
-                Err(FromEmacsqlError::InvalidType)
-            }
+            Value::Lisp(sexp) => sexp,
+            Value::Integer(n) => lisp::Value::Integer(n),
+            Value::Real(n) => lisp::Value::Real(n),
+            Value::Null => lisp::Value::Nil,
+            Value::Blob(bytes) => lisp::Value::Unibyte(bytes),
+        }
+    }
+}
+
+impl FromLisp for lisp::Value {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        Ok(sexp)
+    }
+}
+
+impl<T: FromLisp> FromLisp for Option<T> {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        match sexp {
+            lisp::Value::Nil => Ok(None),
+            sexp => T::from_lisp(sexp).map(Some),
+        }
+    }
+}
+
+impl FromLisp for bool {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        match sexp {
+            lisp::Value::Nil => Ok(false),
+            lisp::Value::Symbol(s) if s == "t" => Ok(true),
+            _ => Err(FromEmacsqlError::InvalidType),
         }
     }
 }
@@ -78,10 +202,15 @@ impl FromEmacsql for bool {
 macro_rules! some_integer_impls {
     ($( $for:ident ),*) => {
         $(
-            impl FromEmacsql for $for {
-                fn from_emacsql(val: Value) -> FromEmacsqlResult<Self> {
-                    match val {
-                        Value::Integer(n) => Ok(n as $for),
+            impl FromLisp for $for {
+                fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+                    match sexp {
+                        lisp::Value::Integer(n) => {
+                            $for::try_from(n).or(Err(FromEmacsqlError::OutOfRange(n)))
+                        }
+                        lisp::Value::BigInt(n) => {
+                            n.parse().map_err(|err| FromEmacsqlError::Other(Box::new(err)))
+                        }
                         _ => Err(FromEmacsqlError::InvalidType),
                     }
                 }
@@ -90,15 +219,15 @@ macro_rules! some_integer_impls {
     };
 }
 
-some_integer_impls![i8, i16, i32, i64, usize];
+some_integer_impls![i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, usize];
 
 macro_rules! some_real_impls {
     ($( $for:ident ),*) => {
         $(
-            impl FromEmacsql for $for {
-                fn from_emacsql(val: Value) -> FromEmacsqlResult<Self> {
-                    match val {
-                        Value::Real(n) => Ok(n as $for),
+            impl FromLisp for $for {
+                fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+                    match sexp {
+                        lisp::Value::Real(n) => Ok(n as $for),
                         _ => Err(FromEmacsqlError::InvalidType),
                     }
                 }
@@ -109,44 +238,286 @@ macro_rules! some_real_impls {
 
 some_real_impls![f32, f64];
 
-pub trait FromLisp: Sized {
-    fn from_lisp(s: lisp::Value) -> FromEmacsqlResult<Self>;
+/// Characters are integers in Emacs, `?a` is read as 97.
+impl FromLisp for char {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        match sexp {
+            lisp::Value::Integer(n) => u32::try_from(n)
+                .ok()
+                .and_then(char::from_u32)
+                .ok_or(FromEmacsqlError::OutOfRange(n)),
+            _ => Err(FromEmacsqlError::InvalidType),
+        }
+    }
 }
 
-impl<T: FromLisp> FromEmacsql for T {
-    fn from_emacsql(val: Value) -> FromEmacsqlResult<Self> {
-        match val {
-            Value::Lisp(s) => T::from_lisp(s).or(Err(FromEmacsqlError::InvalidType)),
-            _ => Err(FromSqlError::InvalidType),
+impl FromLisp for String {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        match sexp {
+            lisp::Value::String(s) | lisp::Value::Propertized(s, _) => Ok(s),
+            lisp::Value::Unibyte(bytes) => {
+                String::from_utf8(bytes).or(Err(FromEmacsqlError::InvalidType))
+            }
+            _ => Err(FromEmacsqlError::InvalidType),
         }
     }
 }
 
-impl FromLisp for String {
+/// A list or a vector, `nil` is the empty list.  A unibyte string is a
+/// sequence of its bytes, like in Emacs, so it can be read as `Vec<u8>`.
+impl<T: FromLisp> FromLisp for Vec<T> {
     fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
         match sexp {
-            lisp::Value::String(s) => Ok(s),
+            lisp::Value::List(items) | lisp::Value::Vector(items) => {
+                items.into_iter().map(T::from_lisp).collect()
+            }
+            lisp::Value::Unibyte(bytes) => bytes
+                .into_iter()
+                .map(|byte| T::from_lisp(lisp::Value::Integer(byte.into())))
+                .collect(),
+            lisp::Value::Nil => Ok(Vec::new()),
             _ => Err(FromEmacsqlError::InvalidType),
         }
     }
 }
 
-pub trait FromLispAsFromStr: Sized + FromStr {}
+/// Return the items of a list or a vector which has exactly `len` items.  A
+/// cons cell `(a . b)` is a pair too.
+fn tuple_items(sexp: lisp::Value, len: usize) -> FromEmacsqlResult<Vec<lisp::Value>> {
+    let items = match sexp {
+        lisp::Value::List(items) | lisp::Value::Vector(items) => items,
+        lisp::Value::Cons(car, cdr) if !matches!(*cdr, lisp::Value::Cons(..)) => {
+            vec![*car, *cdr]
+        }
+        _ => return Err(FromEmacsqlError::InvalidType),
+    };
+    if items.len() != len {
+        return Err(FromEmacsqlError::InvalidType);
+    }
+    Ok(items)
+}
 
-// TODO: implement `FromLisp` for lisp list and vector
-// impl<T: FromLisp> FromLisp for Vec<T> {
-//     fn from_lisp(s: String) -> FromEmacsqlResult<Self> {
-//         match parse_lisp_program(&s) {
-//             Ok(LispObject::)
-//         }
-//     }
-// }
+macro_rules! some_tuple_impls {
+    ($( ($len:literal: $( $name:ident ),+) ),*) => {
+        $(
+            impl<$( $name: FromLisp ),+> FromLisp for ($( $name, )+) {
+                fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+                    let mut items = tuple_items(sexp, $len)?.into_iter();
+                    Ok(($( $name::from_lisp(items.next().unwrap())?, )+))
+                }
+            }
+        )*
+    };
+}
+
+some_tuple_impls![
+    (1: A),
+    (2: A, B),
+    (3: A, B, C),
+    (4: A, B, C, D),
+    (5: A, B, C, D, E),
+    (6: A, B, C, D, E, F)
+];
+
+/// Return the name of a map key: a string or a symbol, the leading colon of a
+/// keyword is dropped, so both `(:id 1)` and `(("id" . 1))` have the key "id".
+fn map_key(key: lisp::Value) -> FromEmacsqlResult<String> {
+    match key {
+        lisp::Value::String(s) | lisp::Value::Propertized(s, _) => Ok(s),
+        lisp::Value::Symbol(s) => match s.strip_prefix(':') {
+            Some(name) => Ok(name.to_string()),
+            None => Ok(s),
+        },
+        _ => Err(FromEmacsqlError::InvalidType),
+    }
+}
+
+/// Return the key-value pairs of an alist, a plist or a hash table.
+fn map_entries<T: FromLisp>(
+    sexp: lisp::Value,
+) -> FromEmacsqlResult<impl Iterator<Item = FromEmacsqlResult<(String, T)>>> {
+    let pairs = lisp::into_pairs(sexp).or(Err(FromEmacsqlError::InvalidType))?;
+    Ok(pairs
+        .into_iter()
+        .map(|(key, val)| Ok((map_key(key)?, T::from_lisp(val)?))))
+}
+
+/// An alist like `(("CATEGORY" . "x"))`, a plist like `(:id 1)` or a hash
+/// table.
+impl<T: FromLisp, S: BuildHasher + Default> FromLisp for HashMap<String, T, S> {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        map_entries(sexp)?.collect()
+    }
+}
+
+/// The same as `HashMap`, but keeps keys sorted.
+impl<T: FromLisp> FromLisp for BTreeMap<String, T> {
+    fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
+        map_entries(sexp)?.collect()
+    }
+}
+
+/// Types which are read with [`FromStr`] from the text of a string or a
+/// symbol.
+///
+/// It maps symbols to C-like enums: implement `FromStr` for an enum, then add
+/// `impl FromLispAsFromStr for Todo {}` and `todo` is read as `Todo::Todo`.
+pub trait FromLispAsFromStr: Sized + FromStr {}
 
 impl<T: FromLispAsFromStr> FromLisp for T {
     fn from_lisp(sexp: lisp::Value) -> FromEmacsqlResult<Self> {
         match sexp {
-            lisp::Value::String(s) => s.parse().or(Err(FromEmacsqlError::InvalidType)),
+            lisp::Value::String(s) | lisp::Value::Symbol(s) => {
+                s.parse().or(Err(FromEmacsqlError::InvalidType))
+            }
             _ => Err(FromEmacsqlError::InvalidType),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from<T: FromLisp>(src: &str) -> FromEmacsqlResult<T> {
+        T::from_lisp(src.parse().unwrap())
+    }
+
+    #[test]
+    fn test_scalars_from_lisp() {
+        assert_eq!(from::<bool>("t"), Ok(true));
+        assert_eq!(from::<bool>("nil"), Ok(false));
+        assert!(from::<bool>("\"t\"").is_err());
+        assert_eq!(from::<char>("?a"), Ok('a'));
+        assert_eq!(from::<u8>("255"), Ok(255));
+        assert_eq!(from::<u8>("256"), Err(FromEmacsqlError::OutOfRange(256)));
+        assert_eq!(from::<Option<String>>("nil"), Ok(None));
+        assert_eq!(from::<Option<String>>("\"x\""), Ok(Some("x".into())));
+    }
+
+    #[test]
+    fn test_bignums_from_lisp() {
+        assert_eq!(from::<u64>("18446744073709551615"), Ok(u64::MAX));
+        assert_eq!(from::<i128>("-18446744073709551616"), Ok(-(1 << 64)));
+        assert!(from::<i64>("18446744073709551615").is_err());
+    }
+
+    #[test]
+    fn test_sequences_from_lisp() {
+        assert_eq!(
+            from::<Vec<String>>("(\"Projects\" \"Rust\")"),
+            Ok(vec!["Projects".into(), "Rust".into()])
+        );
+        assert_eq!(from::<Vec<i64>>("[1 2 3]"), Ok(vec![1, 2, 3]));
+        assert_eq!(from::<Vec<i64>>("nil"), Ok(vec![]));
+        assert_eq!(from::<Vec<Option<i64>>>("(1 nil)"), Ok(vec![Some(1), None]));
+        assert!(from::<Vec<i64>>("(1 . 2)").is_err());
+    }
+
+    #[test]
+    fn test_tuples_from_lisp() {
+        assert_eq!(from::<(String, i64)>("(\"a\" . 1)"), Ok(("a".into(), 1)));
+        assert_eq!(from::<(i64, f64, bool)>("[1 2.5 t]"), Ok((1, 2.5, true)));
+        assert!(from::<(i64, i64)>("(1 2 3)").is_err());
+    }
+
+    #[test]
+    fn test_maps_from_lisp() {
+        let props: HashMap<String, String> =
+            from("((\"CATEGORY\" . \"momentum\") (\"ID\" . \"1\"))").unwrap();
+        assert_eq!(props["CATEGORY"], "momentum");
+        assert_eq!(props["ID"], "1");
+
+        let plist: BTreeMap<String, i64> = from("(:level 2 :pos 40)").unwrap();
+        assert_eq!(
+            plist.into_iter().collect::<Vec<_>>(),
+            vec![("level".into(), 2), ("pos".into(), 40)]
+        );
+
+        let table: BTreeMap<String, i64> =
+            from("#s(hash-table test equal data (\"a\" 1))").unwrap();
+        assert_eq!(table["a"], 1);
+        assert_eq!(from::<BTreeMap<String, i64>>("nil"), Ok(BTreeMap::new()));
+        assert!(from::<BTreeMap<String, i64>>("(1 2)").is_err());
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Todo {
+        Todo,
+        Done,
+    }
+
+    impl FromStr for Todo {
+        type Err = ();
+
+        fn from_str(s: &str) -> Result<Self, ()> {
+            match s {
+                "todo" => Ok(Self::Todo),
+                "done" => Ok(Self::Done),
+                _ => Err(()),
+            }
+        }
+    }
+
+    impl FromLispAsFromStr for Todo {}
+
+    #[test]
+    fn test_symbol_to_enum() {
+        assert_eq!(from::<Todo>("todo"), Ok(Todo::Todo));
+        assert_eq!(
+            from::<Vec<Todo>>("(done todo)"),
+            Ok(vec![Todo::Done, Todo::Todo])
+        );
+        assert!(from::<Todo>("wait").is_err());
+    }
+
+    #[test]
+    fn test_from_emacsql_column() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE nodes (olp, properties, level, scheduled);
+INSERT INTO nodes VALUES ('("Projects" "Rust")', '(("ID" . "1"))', 2, NULL);
+"#,
+        )
+        .unwrap();
+        let (olp, props, level, scheduled) = conn
+            .query_row("SELECT * FROM nodes", [], |row| {
+                let row = crate::Row::from(row);
+                Ok((
+                    row.get::<_, Vec<String>>("olp")?,
+                    row.get::<_, HashMap<String, String>>("properties")?,
+                    row.get::<_, Option<i64>>("level")?,
+                    row.get::<_, Option<String>>("scheduled")?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(olp, vec!["Projects", "Rust"]);
+        assert_eq!(props["ID"], "1");
+        assert_eq!(level, Some(2));
+        assert_eq!(scheduled, None);
+    }
+
+    #[test]
+    fn test_non_utf8_columns() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            r#"
+CREATE TABLE files (file, hash);
+INSERT INTO files VALUES (CAST(X'22FF22' AS TEXT), X'0102');
+"#,
+        )
+        .unwrap();
+        let (file, hash) = conn
+            .query_row("SELECT * FROM files", [], |row| {
+                let row = crate::Row::from(row);
+                Ok((
+                    row.get::<_, lisp::Value>("file")?,
+                    row.get::<_, Vec<u8>>("hash")?,
+                ))
+            })
+            .unwrap();
+        assert_eq!(file, lisp::Value::Unibyte(vec![0xff]));
+        assert_eq!(hash, [1, 2]);
+    }
+}
@@ -1,4 +1,4 @@
-use emacsql::QueryAs;
+use emacsql::{QueryTyped, ToEmacsql};
 
 use rusqlite::Connection;
 use std::fs::File;
@@ -8,36 +8,25 @@ use crate::result::{Error, Result};
 use crate::tag::Tag;
 
 // NOTE: I am not use columns from the table Node which for me useless
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, emacsql::FromRow)]
 pub struct Node {
     /// the identifier of a node.  This is the value of the propertry ID in an `org-mode` heading
     id: Option<ID>,
     /// the title of a node.  This is the title of the `org-mode` heading which refered by node
     title: Option<String>,
     /// name of the file in which stored a node
+    #[emacsql(rename = "file")]
     filename: Option<String>,
     /// list of the node's tags
+    #[emacsql(skip)]
     tags: Option<Vec<Tag>>,
 }
 
-impl emacsql::FromRow for Node {
-    fn try_from_row(row: &emacsql::Row) -> emacsql::Result<Self> {
-        let node = Self {
-            id: row.get("id").ok(),
-            title: row.get("title").ok(),
-            filename: row.get("file").ok(),
-            tags: None,
-        };
-        Ok(node)
-    }
-}
-
 impl Node {
     /// create a `Node` instance that referes to the `org-roam` node with a given ID
     pub fn by_id(id: ID, conn: &mut Connection) -> Result<Self> {
-        let q = r#"SELECT id, title, file FROM nodes WHERE nodes.id = "$1""#;
-        conn.prepare(q)?
-            .query_as_one([id])
+        let q = "SELECT id, title, file FROM nodes WHERE nodes.id = ?1";
+        conn.query_typed_one(q, [id.to_emacsql()])
             .map_err(|err| match err {
                 emacsql::Error::QueryReturnedNoRows => Error::NodeNotFound,
                 _ => Error::DBError(err),
@@ -49,17 +38,12 @@ impl Node {
     where
         T: Into<String>,
     {
-        let q = format!(
-            r#"
-SELECT id, title, file FROM nodes
-WHERE nodes.title = '"{}"'
-"#,
-            title.into()
-        );
-        conn.prepare(&q)?.query_as_one([]).map_err(|err| match err {
-            emacsql::Error::QueryReturnedNoRows => Error::NodeNotFound,
-            _ => Error::DBError(err),
-        })
+        let q = "SELECT id, title, file FROM nodes WHERE nodes.title = ?1";
+        conn.query_typed_one(q, [title.into().to_emacsql()])
+            .map_err(|err| match err {
+                emacsql::Error::QueryReturnedNoRows => Error::NodeNotFound,
+                _ => Error::DBError(err),
+            })
     }
 
     /// return the opened file in which stored a node
@@ -85,8 +69,8 @@ WHERE nodes.title = '"{}"'
             return Ok(tgs.to_owned());
         }
         let id = self.id.as_ref().ok_or(Error::TagNotFound)?;
-        let q = format!("SELECT tag FROM tags WHERE node_id = '{id}'");
-        conn.prepare(&q)?.query_as([]).map_err(Error::DBError)
+        conn.query_typed("SELECT tag FROM tags WHERE node_id = ?1", [id.to_emacsql()])
+            .map_err(Error::DBError)
     }
 
     /// return the ID of a node which consists of 5 parts separated with dash.
@@ -114,9 +98,11 @@ WHERE nodes.title = '"{}"'
     ///
     /// use limit and offset to concretize amount of expected nodes.
     pub fn all_nodes(limit: usize, offset: usize, conn: &mut Connection) -> Result<Vec<Node>> {
-        conn.prepare("SELECT file, title, id FROM nodes LIMIT $1 OFFSET $2")?
-            .query_as([limit, offset])
-            .map_err(Error::DBError)
+        conn.query_typed(
+            "SELECT file, title, id FROM nodes LIMIT $1 OFFSET $2",
+            [limit, offset],
+        )
+        .map_err(Error::DBError)
     }
 
     /// return all nodes, that have a given tag.
@@ -124,41 +110,37 @@ WHERE nodes.title = '"{}"'
         let q = r#"
 SELECT file, title, id
 FROM nodes
-WHERE id in (SELECT node_id FROM tags WHERE tag = $1)"#;
-        conn.prepare(q)?
-            .query_as([tag.name()])
+WHERE id in (SELECT node_id FROM tags WHERE tag = ?1)"#;
+        conn.query_typed(q, [tag.name().to_emacsql()])
             .map_err(Error::DBError)
     }
 
+    /// returns the vector of nodes that the current node refers to, each node
+    /// once even when it's linked several times
     pub fn refers_to(&self, conn: &Connection) -> Result<Vec<Node>> {
         let id = self.id.as_ref().ok_or(Error::NodeIdNotFetched)?;
-        let q = format!(
-            r#"
-SELECT id, title, file
+        let q = r#"
+SELECT DISTINCT id, title, file
 FROM links AS l
 JOIN nodes AS n
 ON l.dest = n.id
-WHERE l.source = '"{}"'
-"#,
-            &id
-        );
-        conn.prepare(&q)?.query_as([]).map_err(Error::DBError)
+WHERE l.source = ?1"#;
+        conn.query_typed(q, [id.to_emacsql()])
+            .map_err(Error::DBError)
     }
 
-    /// returns the vector of nodes that refers to the current node
+    /// returns the vector of nodes that refers to the current node, each node
+    /// once even when it links here several times
     pub fn backlinks(&self, conn: &mut Connection) -> Result<Vec<Node>> {
         let id = self.id.as_ref().ok_or(Error::NodeIdNotFetched)?;
-        let q = format!(
-            r#"
-SELECT id, title, file
+        let q = r#"
+SELECT DISTINCT id, title, file
 FROM links AS l
 JOIN nodes AS n
 ON l.source = n.id
-WHERE l.dest = '"{}"'
-"#,
-            &id
-        );
-        conn.prepare(&q)?.query_as([]).map_err(Error::DBError)
+WHERE l.dest = ?1"#;
+        conn.query_typed(q, [id.to_emacsql()])
+            .map_err(Error::DBError)
     }
 }
 
@@ -263,6 +245,7 @@ mod tests {
             .map(Node::title)
             .map(Result::unwrap)
             .collect();
+        // the test database has two links to this node from node 4
         assert_eq!(parents_names, ["Second Law of Newton"]);
     }
 
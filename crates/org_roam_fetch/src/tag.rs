@@ -1,20 +1,15 @@
-use emacsql::query::QueryAs;
+use emacsql::query::QueryTyped;
+use emacsql::ToEmacsql;
 
 use crate::result::{Error, Result};
 use rusqlite::Connection;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, emacsql::FromRow)]
 pub struct Tag {
+    #[emacsql(rename = "tag")]
     name: String,
 }
 
-impl<'a> emacsql::FromRow for Tag {
-    fn try_from_row(row: &emacsql::Row) -> emacsql::Result<Self> {
-        let name: String = row.get("tag")?;
-        Ok(Tag::new(name))
-    }
-}
-
 impl Tag {
     pub fn new<T>(name: T) -> Self
     where
@@ -24,8 +19,7 @@ impl Tag {
     }
 
     pub fn by_name(name: &str, conn: &mut Connection) -> Result<Self> {
-        conn.prepare("SELECT tag FROM tags WHERE tag = $1")?
-            .query_as_one([name])
+        conn.query_typed_one("SELECT tag FROM tags WHERE tag = ?1", [name.to_emacsql()])
             .map_err(|err| {
                 if let emacsql::Error::QueryReturnedNoRows = err {
                     Error::TagNotFound
@@ -40,8 +34,7 @@ impl Tag {
     }
 
     pub fn all_tags(conn: &mut Connection) -> Result<Vec<Self>> {
-        conn.prepare("SELECT DISTINCT tag FROM tags")?
-            .query_as([])
+        conn.query_typed("SELECT DISTINCT tag FROM tags", [])
             .map_err(Error::DBError)
     }
 }